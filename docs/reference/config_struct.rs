@@ -1,21 +1,29 @@
 struct ConfigFile {
     /// Array of globs to match for passthrough-copying.
     ///
+    /// Like every array of strings in this file, variables are interpolated. A `$` must begin one of:
+    /// - $PROJECT_ROOT, $CONTENT_ROOT, $OUTPUT_ROOT, or $TEMPLATE_ROOT, which are replaced with the respective full paths.
+    ///   Braces are optional, e.g. `${PROJECT_ROOT}`.
+    /// - ${env:NAME}, which is replaced with the environment variable NAME. Errors if unset.
+    /// - ${env:NAME:-default}, which is replaced with the environment variable NAME, or "default" if unset.
+    /// - $$, which is replaced with a literal $.
+    ///
+    /// Globs starting with `!` stop matching files instead, e.g. to copy `assets/**` except `assets/**/*.psd`.
+    /// The last glob that matches a file decides whether it is copied.
+    ///
     /// Example in the TOML config file: `passthrough_copy = ["*.css", "*.js", "assets/*"]
     passthrough_copy: Vec<String>,
     /// Command to run before a full rebuild.
     ///
-    /// Strings should not contain $ unless the symbol begins:
-    /// - $PROJECT_ROOT, which is replaced with the path to the project root.
+    /// Variables are interpolated as described in `passthrough_copy`.
     ///
-    /// E.g., `passthrough_copy = ["python", "$PROJECT_ROOT/prebuild.py"]`.
+    /// E.g., `init = ["python", "$PROJECT_ROOT/prebuild.py"]`.
     init: Vec<String>,
     /// Command to run to post-process HTML files generated by Typst.
     ///
     /// Must take in stdin and return via stdout.
     ///
-    /// Strings should not contain $ unless the symbol begins:
-    /// - $PROJECT_ROOT, which is replaced with the path to the project root.
+    /// Variables are interpolated as described in `passthrough_copy`.
     ///
     /// Example in the TOML config file: `post_processing_typ = ["python", "$PROJECT_ROOT/post_processing_script.py"]`.
     post_processing_typ: Vec<String>,
@@ -55,4 +63,137 @@ struct ConfigFile {
     ///
     /// Example in the TOML config file: `disable_incremental = true`
     disable_incremental: bool,
+    /// Directory, relative to the project root, holding the site's content. Defaults to "src".
+    ///
+    /// Example in the TOML config file: `content_dir = "content"`
+    content_dir: Option<PathBuf>,
+    /// Directory, relative to the project root, that the site is written to. Defaults to "_site".
+    ///
+    /// Example in the TOML config file: `output_dir = "public"`
+    output_dir: Option<PathBuf>,
+    /// Directory, relative to the project root, holding templates. Defaults to "templates".
+    ///
+    /// Changes to files in this directory recompile the pages that use them while watching.
+    ///
+    /// Example in the TOML config file: `template_dir = "layouts"`
+    template_dir: Option<PathBuf>,
+    /// Ignore keys in the config file that `compile-typst-site` doesn't know about, instead of erroring.
+    ///
+    /// This can be helpful for sharing a config file with newer versions of `compile-typst-site`.
+    ///
+    /// Example in the TOML config file: `allow_unknown_keys = true`
+    allow_unknown_keys: bool,
+    /// Values passed to every `typst compile` and `typst query` as `sys.inputs`.
+    ///
+    /// Values are interpolated as described in `passthrough_copy`.
+    /// While watching or serving, `cts-mode` is set to "dev" unless given otherwise.
+    ///
+    /// Example in the TOML config file:
+    /// ```toml
+    /// [inputs]
+    /// base_url = "https://example.com"
+    /// site_title = "My Site"
+    /// ```
+    inputs: BTreeMap<String, String>,
+    /// Settings that apply only to source files matching a glob.
+    ///
+    /// Globs are rooted in the content directory, like `passthrough_copy`.
+    /// Each section may set `literal_paths`, `post_processing_typ`, `compilation_extra_args`, and `[inputs]`,
+    /// which replace the top-level settings for matching files. Inputs are merged key by key.
+    /// When several sections match a file, the most specific glob (the one with the most non-wildcard characters) wins,
+    /// with ties going to the section written last.
+    ///
+    /// Example in the TOML config file:
+    /// ```toml
+    /// [[override]]
+    /// glob = "legacy/**"
+    /// literal_paths = true
+    ///
+    /// [[override]]
+    /// glob = "slides/**"
+    /// compilation_extra_args = ["--ignore-system-fonts", "--ppi", "300"]
+    /// ```
+    overrides: Vec<OverrideFile>,
+    /// Array of globs matching files under the content root to pretend don't exist.
+    ///
+    /// Excluded files are neither compiled, passthrough-copied, listed, nor watched.
+    /// Globs are rooted in the content directory, like `passthrough_copy`.
+    ///
+    /// Example in the TOML config file: `exclude = ["**/README.typ", "vendor/**", "scratch/**"]`
+    exclude: Vec<String>,
+    /// Also exclude files matching patterns in `.gitignore` and `.ctsignore` files.
+    ///
+    /// Ignore files are read from the project root and every directory under the content root.
+    /// Patterns follow `.gitignore` syntax, including `!` to re-include a file.
+    ///
+    /// Example in the TOML config file: `use_ignore_files = true`
+    use_ignore_files: bool,
+    /// Typst files under the content root whose name, or the name of any parent directory, starts with this prefix
+    /// are partials: they can be imported by pages, but aren't compiled to pages themselves.
+    /// Changes to partials recompile the pages that import them while watching.
+    ///
+    /// Defaults to "_". Set to false to treat every Typst file as a page.
+    ///
+    /// Example in the TOML config file: `partial_prefix = "partial-"`
+    partial_prefix: Option<String>,
+    /// Rules to copy files from anywhere to anywhere in the output directory.
+    ///
+    /// Unlike `passthrough_copy`, `from` is a glob rooted in the project root, so it can match files outside the content directory.
+    /// Matched files are copied into the `to` directory, relative to the output root,
+    /// keeping their paths relative to the non-wildcard start of `from`.
+    /// If `from` has no wildcards and `to` doesn't end in `/`, the file is copied to exactly `to` instead.
+    /// Variables are interpolated as described in `passthrough_copy`. The first matching rule wins, before `passthrough_copy`.
+    ///
+    /// Example in the TOML config file:
+    /// ```toml
+    /// [[passthrough_map]]
+    /// from = "node_modules/katex/dist/**"
+    /// to = "vendor/katex/"
+    ///
+    /// [[passthrough_map]]
+    /// from = "../shared-assets/*"
+    /// to = "assets/"
+    /// ```
+    passthrough_map: Vec<PassthroughMapFile>,
+    /// Match `passthrough_copy` and `[[passthrough_map]]` globs case-insensitively.
+    ///
+    /// Example in the TOML config file: `passthrough_ignore_case = true`
+    passthrough_ignore_case: bool,
+    /// Don't let wildcards in `passthrough_copy` and `[[passthrough_map]]` globs match names starting with `.`,
+    /// so dotfiles are only copied if written out, e.g. `.well-known/*`.
+    ///
+    /// Example in the TOML config file: `passthrough_skip_dotfiles = true`
+    passthrough_skip_dotfiles: bool,
+    /// How many Typst processes to run at once when compiling or querying.
+    ///
+    /// Defaults to the available parallelism of your computer.
+    ///
+    /// Example in the TOML config file: `jobs = 4`
+    jobs: Option<usize>,
+    /// After a full build, delete files in the output directory that no source file compiles or copies to anymore,
+    /// e.g. because the source was deleted or renamed. Also turned on by `--prune-stale`.
+    ///
+    /// Example in the TOML config file: `prune_stale = true`
+    prune_stale: bool,
+    /// Array of globs matching files in the output directory that `prune_stale` must never delete,
+    /// e.g. files put there by another tool.
+    ///
+    /// Globs are rooted in the output directory. Variables are interpolated as described in `passthrough_copy`.
+    ///
+    /// Example in the TOML config file: `preserve = ["CNAME", ".well-known/**"]`
+    preserve: Vec<String>,
+    /// Build the whole site in a directory next to the output directory, starting from a copy of it,
+    /// and swap it in for the output directory only if every file compiled. Also turned on by `--staged-builds`.
+    ///
+    /// A failed build then leaves the previous site untouched, instead of partly updated.
+    ///
+    /// Example in the TOML config file: `staged_builds = true`
+    staged_builds: bool,
+    /// Array of Typst warnings to never show, matched against their messages.
+    /// A warning is suppressed if its message contains any of these.
+    ///
+    /// Variables are interpolated as described in `passthrough_copy`.
+    ///
+    /// Example in the TOML config file: `suppress_warnings = ["html export is under active development"]`
+    suppress_warnings: Vec<String>,
 }
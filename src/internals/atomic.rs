@@ -0,0 +1,64 @@
+//! Write files so that readers, like the dev server or an rsync, never see them half-written.
+//!
+//! Everything is written to a temporary file next to its destination and then renamed over it,
+//! which replaces the destination all at once.
+
+use anyhow::{Context as _, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A fresh path next to `path`, so renaming it to `path` doesn't cross filesystems.
+fn temp_path(path: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let name = path
+        .file_name()
+        .map_or_else(|| "output".into(), |name| name.to_string_lossy());
+    path.with_file_name(format!(
+        ".{name}.cts-tmp-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Rename `temp` to `path` if `written` succeeded, or else clean it up.
+fn finish(temp: &Path, path: &Path, written: std::io::Result<()>) -> Result<()> {
+    let renamed = written.and_then(|()| fs::rename(temp, path));
+    if renamed.is_err() {
+        let _dont_validate_removal = fs::remove_file(temp);
+    }
+    renamed.context(format!("Failed to write output to {path:?}"))
+}
+
+/// Like [`fs::write`], but `path` is replaced all at once.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let temp = temp_path(path);
+    let written = fs::write(&temp, contents);
+    finish(&temp, path, written)
+}
+
+/// Like [`fs::copy`], but `to` is replaced all at once.
+pub fn copy(from: &Path, to: &Path) -> Result<()> {
+    let temp = temp_path(to);
+    let written = fs::copy(from, &temp).map(|_| ());
+    finish(&temp, to, written)
+}
+
+/// Recreate the directory `from` at `to`, hard linking files where possible.
+///
+/// Since everything is written by renaming over files, never into them, later writes to `to` don't change `from`.
+pub fn link_dir(from: &Path, to: &Path) -> Result<()> {
+    for entry in walkdir::WalkDir::new(from) {
+        let entry = entry?;
+        let dst = to.join(entry.path().strip_prefix(from)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&dst)?;
+        } else if fs::hard_link(entry.path(), &dst).is_err() {
+            fs::copy(entry.path(), &dst)
+                .context(format!("Failed to copy {:?} to {dst:?}", entry.path()))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,288 @@
+//! Remember what each page was compiled from, so later builds can skip pages that haven't changed.
+//!
+//! The cache lives in [`CACHE_DIR`](crate::internals::config::CACHE_DIR) under the project root. A page is skipped when
+//! its output still exists, it would be compiled with the same arguments, and every file it read when it was cached
+//! has the same contents. Everything is thrown away when the version of Typst or compile-typst-site changes.
+//!
+//! Contents and arguments are hashed with 128-bit SipHash-1-3 over their bytes, which, unlike `std`'s hashers and
+//! `Hash` implementations, is specified, so hashes mean the same across builds of compile-typst-site.
+
+use anyhow::{Context as _, Result};
+use json::JsonValue;
+use siphasher::sip128::{Hasher128 as _, SipHasher13};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::hash::Hasher as _;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::internals::atomic;
+use crate::internals::config::Config;
+
+const CACHE_FNAME: &str = "pages.json";
+
+/// How hashes are made. Part of the cache's versions, so changing it throws away hashes made the old way.
+const HASHER: &str = "siphash13-128";
+
+struct Cache {
+    /// Where to save the cache.
+    path: PathBuf,
+    /// The versions of Typst and compile-typst-site, and the [`HASHER`], the cache was made with.
+    versions: String,
+    pages: BTreeMap<PathBuf, Entry>,
+    /// Whether `pages` changed since the cache was loaded or saved.
+    dirty: bool,
+}
+
+#[derive(Clone)]
+struct Entry {
+    fingerprint: String,
+    /// The hash of the contents of every file the page read, including itself.
+    deps: BTreeMap<PathBuf, String>,
+}
+
+/// `None` when caching is turned off or the cache hasn't been loaded.
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+/// Hashes of files' contents, so files many pages read, like templates, are only read once per batch.
+/// `None` if the file couldn't be read.
+static FILE_HASHES: Mutex<BTreeMap<PathBuf, Option<String>>> = Mutex::new(BTreeMap::new());
+
+/// How many pages were skipped since the cache was last saved.
+static HITS: AtomicUsize = AtomicUsize::new(0);
+
+fn hash(bytes: &[u8]) -> String {
+    let mut hasher = SipHasher13::new();
+    hasher.write(bytes);
+    format!("{:032x}", hasher.finish128().as_u128())
+}
+
+fn hash_file(path: &Path) -> Option<String> {
+    if let Some(known) = FILE_HASHES
+        .lock()
+        .expect("nothing panics with the lock")
+        .get(path)
+    {
+        return known.clone();
+    }
+
+    // don't hold the lock while reading the file.
+    let hashed = fs::read(path).ok().map(|contents| hash(&contents));
+    FILE_HASHES
+        .lock()
+        .expect("nothing panics with the lock")
+        .insert(path.to_path_buf(), hashed.clone());
+    hashed
+}
+
+/// Summarize everything a page is compiled with, other than the files it reads.
+pub fn fingerprint(args: &[&[&OsStr]]) -> String {
+    // length-prefixed, so e.g. `["ab"]` and `["a", "b"]` differ.
+    let mut bytes = Vec::new();
+    for group in args {
+        bytes.extend((group.len() as u64).to_le_bytes());
+        for arg in *group {
+            let arg = arg.to_string_lossy();
+            bytes.extend((arg.len() as u64).to_le_bytes());
+            bytes.extend(arg.as_bytes());
+        }
+    }
+    hash(&bytes)
+}
+
+/// The hashes of the contents of the files `command` names, e.g. a post-processing script, so editing them
+/// changes the [`fingerprint`] of the pages it runs on.
+pub fn command_file_hashes(command: &[String]) -> Vec<String> {
+    command
+        .iter()
+        .map(Path::new)
+        .filter(|path| path.is_file())
+        .filter_map(hash_file)
+        .collect()
+}
+
+/// Load the cache from the project root, if `config` allows it.
+///
+/// A missing or unreadable cache is treated as empty.
+pub fn load(config: &Config, typst_version: &str) {
+    if !config.use_cache {
+        log::debug!("not using the build cache");
+        return;
+    }
+
+    let path = config.cache_root().join(CACHE_FNAME);
+    let versions = format!("{typst_version} {} {HASHER}", env!("CARGO_PKG_VERSION"));
+    let pages = match fs::read_to_string(&path) {
+        Ok(contents) => parse(&contents, &versions).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable build cache {path:?}: {e:?}");
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new(),
+    };
+    log::debug!("loaded {} cached pages from {path:?}", pages.len());
+
+    *CACHE.lock().expect("nothing panics with the lock") = Some(Cache {
+        path,
+        versions,
+        pages,
+        dirty: false,
+    });
+}
+
+fn parse(contents: &str, versions: &str) -> Result<BTreeMap<PathBuf, Entry>> {
+    let cache = json::parse(contents)?;
+    if cache["versions"].as_str() != Some(versions) {
+        log::info!(
+            "Typst, compile-typst-site, or how the cache is hashed changed since the last build, so ignoring the build cache"
+        );
+        return Ok(BTreeMap::new());
+    }
+
+    let mut pages = BTreeMap::new();
+    for (page, entry) in cache["pages"].entries() {
+        let fingerprint = entry["fingerprint"]
+            .as_str()
+            .context("Found a page with no fingerprint.")?
+            .to_string();
+        let deps = entry["deps"]
+            .entries()
+            .map(|(dep, hash)| {
+                let hash = hash.as_str().context("Found a dependency with no hash.")?;
+                Ok((PathBuf::from(dep), hash.to_string()))
+            })
+            .collect::<Result<_>>()?;
+        pages.insert(PathBuf::from(page), Entry { fingerprint, deps });
+    }
+    Ok(pages)
+}
+
+/// If `page` can be skipped, the files it read when it was cached.
+pub fn lookup(page: &Path, fingerprint: &str, output: &Path) -> Option<Vec<PathBuf>> {
+    // don't hold the lock while hashing.
+    let entry = CACHE
+        .lock()
+        .expect("nothing panics with the lock")
+        .as_ref()?
+        .pages
+        .get(page)?
+        .clone();
+
+    if entry.fingerprint != fingerprint || !output.exists() {
+        return None;
+    }
+    for (dep, dep_hash) in &entry.deps {
+        if hash_file(dep).as_ref() != Some(dep_hash) {
+            log::debug!("{page:?} must be recompiled because {dep:?} changed");
+            return None;
+        }
+    }
+
+    HITS.fetch_add(1, Ordering::Relaxed);
+    Some(entry.deps.into_keys().collect())
+}
+
+/// Record that `page` compiled successfully with `fingerprint` after reading `deps`.
+pub fn store(page: &Path, fingerprint: String, deps: &[PathBuf]) {
+    if CACHE
+        .lock()
+        .expect("nothing panics with the lock")
+        .is_none()
+    {
+        return;
+    }
+
+    let mut hashes = BTreeMap::new();
+    for dep in deps.iter().map(PathBuf::as_path).chain([page]) {
+        let Some(dep_hash) = hash_file(dep) else {
+            // we can't tell if it changes later, so don't cache the page at all.
+            forget(page);
+            return;
+        };
+        hashes.insert(dep.to_path_buf(), dep_hash);
+    }
+
+    if let Some(cache) = CACHE.lock().expect("nothing panics with the lock").as_mut() {
+        cache.pages.insert(
+            page.to_path_buf(),
+            Entry {
+                fingerprint,
+                deps: hashes,
+            },
+        );
+        cache.dirty = true;
+    }
+}
+
+/// Make sure `page`, or every page under it if it's a directory, is compiled next time, e.g. because compiling it
+/// failed.
+pub fn forget(page: &Path) {
+    if let Some(cache) = CACHE.lock().expect("nothing panics with the lock").as_mut() {
+        let cached = cache.pages.len();
+        cache.pages.retain(|known, _| !known.starts_with(page));
+        cache.dirty |= cache.pages.len() != cached;
+    }
+}
+
+/// Write the cache back to the project root if it changed.
+///
+/// Also forgets the hashes of files read so far, since they may change before the next batch.
+pub fn save() -> Result<()> {
+    FILE_HASHES
+        .lock()
+        .expect("nothing panics with the lock")
+        .clear();
+    let hits = HITS.swap(0, Ordering::Relaxed);
+    if hits > 0 {
+        log::info!("skipped {hits} pages that were unchanged since they were cached");
+    }
+
+    let mut cache = CACHE.lock().expect("nothing panics with the lock");
+    let Some(cache) = cache.as_mut().filter(|cache| cache.dirty) else {
+        return Ok(());
+    };
+
+    let mut pages = JsonValue::new_object();
+    for (page, entry) in &cache.pages {
+        let mut deps = JsonValue::new_object();
+        for (dep, dep_hash) in &entry.deps {
+            deps[dep.to_string_lossy().as_ref()] = dep_hash.as_str().into();
+        }
+        pages[page.to_string_lossy().as_ref()] = json::object! {
+            fingerprint: entry.fingerprint.as_str(),
+            deps: deps,
+        };
+    }
+    let contents = json::object! {
+        versions: cache.versions.as_str(),
+        pages: pages,
+    };
+
+    let dir = cache.path.parent().context("Found no parent.")?;
+    fs::create_dir_all(dir)?;
+    atomic::write(&cache.path, contents.dump())?;
+    cache.dirty = false;
+
+    Ok(())
+}
+
+/// Delete the build cache of the project.
+pub fn clean(config: &Config) -> Result<()> {
+    let cache_root = config.cache_root();
+    if !cache_root.exists() {
+        log::info!(
+            "no build cache to delete at {}",
+            cache_root.to_string_lossy()
+        );
+        return Ok(());
+    }
+
+    fs::remove_dir_all(&cache_root).context(format!(
+        "Failed to delete build cache at {}",
+        cache_root.to_string_lossy()
+    ))?;
+    log::info!("deleted build cache at {}", cache_root.to_string_lossy());
+
+    Ok(())
+}
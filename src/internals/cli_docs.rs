@@ -0,0 +1,388 @@
+//! Shell completions and a man page, generated from the `--help` text so they can't drift from the real arguments.
+
+use crate::internals::config::{HELP, Shell};
+
+const BIN_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// A flag or option from the help text.
+struct Arg {
+    short: Option<char>,
+    long: String,
+    /// The type of the option's value, e.g. `PATH`, or `None` for flags.
+    value: Option<String>,
+    help: String,
+    possible_values: Vec<String>,
+}
+
+/// A command from the help text of the positional arguments.
+struct Command {
+    name: String,
+    /// The command's own arguments, e.g. `[DIR]`.
+    args: String,
+    help: String,
+    possible_values: Vec<String>,
+}
+
+/// The values listed in `help` as `[possible values: a, b]`.
+fn possible_values(help: &str) -> Vec<String> {
+    help.split_once("[possible values: ")
+        .and_then(|(_, rest)| rest.split_once(']'))
+        .map(|(values, _)| values.split(", ").map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// `help` without notes like `[default: auto]`, which completions show in other ways.
+fn short_help(help: &str) -> String {
+    let mut short = help.to_string();
+    for note in ["[default: ", "[possible values: "] {
+        while let Some(start) = short.find(note) {
+            let end = short[start..]
+                .find(']')
+                .map_or(short.len(), |end| start + end + 1);
+            short.replace_range(start..end, "");
+        }
+    }
+    short.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Parse the flags and options sections of the help text.
+fn args() -> Vec<Arg> {
+    let mut args: Vec<Arg> = Vec::new();
+    let mut in_args = false;
+    for line in HELP.lines() {
+        match line {
+            "Flags:" | "Options:" => in_args = true,
+            "" => in_args = false,
+            _ if in_args => {
+                let line = line.trim_start();
+                // continuations of multi-line docs are indented past the argument names.
+                let Some(names) = line.strip_prefix('-') else {
+                    if let Some(arg) = args.last_mut() {
+                        arg.help = format!("{} {line}", arg.help);
+                    }
+                    continue;
+                };
+                let (names, help) = names.split_once("  ").unwrap_or((names, ""));
+                let mut names = names.split(' ');
+                let (short, long) = match names.next().unwrap_or_default() {
+                    short if !short.starts_with('-') => (
+                        short.chars().next(),
+                        names.next().unwrap_or_default().trim_start_matches('-'),
+                    ),
+                    long => (None, long.trim_start_matches('-')),
+                };
+                args.push(Arg {
+                    short,
+                    long: long.to_string(),
+                    value: names.next().map(str::to_string),
+                    help: help.trim().to_string(),
+                    possible_values: Vec::new(),
+                });
+            }
+            _ => (),
+        }
+    }
+
+    for arg in args.iter_mut() {
+        arg.possible_values = possible_values(&arg.help);
+    }
+    args
+}
+
+/// Parse the commands out of the positional arguments section of the help text.
+fn commands() -> Vec<Command> {
+    let Some((_, positional)) = HELP.split_once("\ncommand:\n") else {
+        return Vec::new();
+    };
+
+    positional
+        .lines()
+        .filter_map(|line| {
+            let (usage, help) = line.trim().split_once(':')?;
+            let (name, args) = usage.split_once(' ').unwrap_or((usage, ""));
+            // the line introducing the commands isn't one.
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase()) {
+                return None;
+            }
+            Some(Command {
+                name: name.to_string(),
+                args: args.to_string(),
+                help: help.trim().to_string(),
+                possible_values: possible_values(help),
+            })
+        })
+        .collect()
+}
+
+/// Generate a completion script for `shell`.
+pub fn completions(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+    }
+}
+
+fn bash() -> String {
+    let args = args();
+    let commands = commands();
+    let function = format!("_{}", BIN_NAME.replace('-', "_"));
+
+    let mut cases = String::new();
+    for arg in args.iter().filter(|arg| arg.value.is_some()) {
+        let mut patterns = format!("--{}", arg.long);
+        if let Some(short) = arg.short {
+            patterns += &format!("|-{short}");
+        }
+        let reply = if !arg.possible_values.is_empty() {
+            format!(
+                "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+                arg.possible_values.join(" ")
+            )
+        } else if arg.value.as_deref() == Some("PATH") {
+            "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string()
+        } else {
+            "COMPREPLY=()".to_string()
+        };
+        cases += &format!(
+            "        {patterns})\n            {reply}\n            return\n            ;;\n"
+        );
+    }
+    for command in commands.iter().filter(|command| !command.args.is_empty()) {
+        let reply = if command.possible_values.is_empty() {
+            "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string()
+        } else {
+            format!(
+                "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+                command.possible_values.join(" ")
+            )
+        };
+        cases += &format!(
+            "        {})\n            {reply}\n            return\n            ;;\n",
+            command.name
+        );
+    }
+
+    let flags: Vec<String> = args
+        .iter()
+        .flat_map(|arg| {
+            arg.short
+                .map(|short| format!("-{short}"))
+                .into_iter()
+                .chain([format!("--{}", arg.long)])
+        })
+        .collect();
+    let command_names: Vec<&str> = commands.iter().map(|c| c.name.as_str()).collect();
+
+    format!(
+        r#"{function}() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+
+    case "$prev" in
+{cases}    esac
+
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{flags}" -- "$cur"))
+    else
+        COMPREPLY=($(compgen -W "{command_names}" -- "$cur"))
+    fi
+}}
+
+complete -F {function} {BIN_NAME}
+"#,
+        flags = flags.join(" "),
+        command_names = command_names.join(" "),
+    )
+}
+
+/// Escape `s` for use in a single-quoted zsh `_arguments` spec.
+fn zsh_escape(s: &str) -> String {
+    s.replace('\'', r"'\''")
+        .replace('[', r"\[")
+        .replace(']', r"\]")
+        .replace(':', r"\:")
+}
+
+fn zsh() -> String {
+    let mut specs = Vec::new();
+    for arg in args() {
+        let names = match arg.short {
+            Some(short) => format!(
+                "'(-{short} --{long})'{{-{short},--{long}}}'",
+                long = arg.long
+            ),
+            None => format!("'--{}", arg.long),
+        };
+        // multivalue options say so in their docs.
+        let names = if arg.help.contains("multiple times") {
+            format!("'*--{}", arg.long)
+        } else {
+            names
+        };
+        let action = match (&arg.value, arg.possible_values.as_slice()) {
+            (None, _) => String::new(),
+            (Some(value), []) if value == "PATH" => format!(":{value}:_files"),
+            (Some(value), []) => format!(":{value}:"),
+            (Some(value), values) => format!(":{value}:({})", values.join(" ")),
+        };
+        specs.push(format!(
+            "{names}[{}]{action}'",
+            zsh_escape(&short_help(&arg.help))
+        ));
+    }
+
+    let commands = commands();
+    let command_list: Vec<String> = commands
+        .iter()
+        .map(|command| {
+            format!(
+                "{}\\:\"{}\"",
+                command.name,
+                zsh_escape(&short_help(&command.help)).replace('"', "\\\"")
+            )
+        })
+        .collect();
+    specs.push(format!("'1:command:(({}))'", command_list.join(" ")));
+    specs.push("'2:argument:->argument'".to_string());
+
+    let mut cases = String::new();
+    for command in commands.iter().filter(|command| !command.args.is_empty()) {
+        let action = if command.possible_values.is_empty() {
+            "_files".to_string()
+        } else {
+            format!(
+                "_values {} {}",
+                command.args.trim_matches(['[', ']']).to_lowercase(),
+                command.possible_values.join(" ")
+            )
+        };
+        cases += &format!("                {}) {action} ;;\n", command.name);
+    }
+
+    format!(
+        r#"#compdef {BIN_NAME}
+
+_{function}() {{
+    local state line
+
+    _arguments -s \
+        {specs}
+
+    case $state in
+        argument)
+            case $line[1] in
+{cases}            esac
+            ;;
+    esac
+}}
+
+_{function} "$@"
+"#,
+        function = BIN_NAME.replace('-', "_"),
+        specs = specs.join(" \\\n        "),
+    )
+}
+
+/// Escape `s` for use in a single-quoted fish string.
+fn fish_escape(s: &str) -> String {
+    s.replace('\\', r"\\").replace('\'', r"\'")
+}
+
+fn fish() -> String {
+    let mut lines = vec![format!("complete -c {BIN_NAME} -f")];
+
+    for arg in args() {
+        let mut line = format!("complete -c {BIN_NAME}");
+        if let Some(short) = arg.short {
+            line += &format!(" -s {short}");
+        }
+        line += &format!(" -l {}", arg.long);
+        match (&arg.value, arg.possible_values.as_slice()) {
+            (None, _) => (),
+            (Some(value), []) if value == "PATH" => line += " -r -F",
+            (Some(_), []) => line += " -x",
+            (Some(_), values) => line += &format!(" -x -a '{}'", values.join(" ")),
+        }
+        line += &format!(" -d '{}'", fish_escape(&short_help(&arg.help)));
+        lines.push(line);
+    }
+
+    let commands = commands();
+    let command_names: Vec<&str> = commands.iter().map(|c| c.name.as_str()).collect();
+    for command in &commands {
+        lines.push(format!(
+            "complete -c {BIN_NAME} -n 'not __fish_seen_subcommand_from {}' -a {} -d '{}'",
+            command_names.join(" "),
+            command.name,
+            fish_escape(&short_help(&command.help))
+        ));
+        if command.args.is_empty() {
+            continue;
+        }
+        let values = if command.possible_values.is_empty() {
+            "-F".to_string()
+        } else {
+            format!("-a '{}'", command.possible_values.join(" "))
+        };
+        lines.push(format!(
+            "complete -c {BIN_NAME} -n '__fish_seen_subcommand_from {}' {values}",
+            command.name
+        ));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// Escape `s` for use as roff text.
+fn roff_escape(s: &str) -> String {
+    let s = s.replace('\\', r"\e").replace('-', r"\-");
+    // lines starting with these would be read as requests.
+    if s.starts_with(['.', '\'']) {
+        format!(r"\&{s}")
+    } else {
+        s
+    }
+}
+
+/// Generate a man page in roff.
+pub fn man() -> String {
+    let mut page = format!(
+        ".TH {} 1 \"\" \"{BIN_NAME} {}\"\n",
+        BIN_NAME.to_uppercase(),
+        env!("CARGO_PKG_VERSION")
+    );
+    page += &format!(
+        ".SH NAME\n{} \\- {}\n",
+        roff_escape(BIN_NAME),
+        roff_escape(env!("CARGO_PKG_DESCRIPTION"))
+    );
+    page += &format!(
+        ".SH SYNOPSIS\n.B {}\n[flags] [options] [command]\n",
+        roff_escape(BIN_NAME)
+    );
+
+    page += ".SH OPTIONS\n";
+    for arg in args() {
+        let mut names = String::new();
+        if let Some(short) = arg.short {
+            names += &format!("\\fB\\-{short}\\fR, ");
+        }
+        names += &format!("\\fB\\-\\-{}\\fR", roff_escape(&arg.long));
+        if let Some(value) = &arg.value {
+            names += &format!(" \\fI{value}\\fR");
+        }
+        page += &format!(".TP\n{names}\n{}\n", roff_escape(&arg.help));
+    }
+
+    page += ".SH COMMANDS\n";
+    for command in commands() {
+        let mut usage = format!("\\fB{}\\fR", command.name);
+        if !command.args.is_empty() {
+            usage += &format!(" \\fI{}\\fR", command.args);
+        }
+        page += &format!(".TP\n{usage}\n{}\n", roff_escape(&command.help));
+    }
+
+    page
+}
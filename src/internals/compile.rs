@@ -2,28 +2,71 @@
 
 use anyhow::{Context as _, Result, anyhow};
 use json::JsonValue;
+use std::collections::BTreeSet;
 use std::ffi::OsStr;
+use std::fmt::Display;
 use std::fs;
 use std::io::Read as _;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::sync::mpsc::{self};
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use walkdir::WalkDir;
 
-use crate::internals::config::{Config, FileListing};
+use crate::internals::config::{Config, FileListing, FileSettings};
+use crate::internals::diagnostics::{self, Diagnostic, Severity};
+use crate::internals::{atomic, cache, deps, jobs, report, timings};
 
 /// Return absolute paths to the files in source we will process.
 ///
 /// This includes data files we ignore, stuff we pass through, typ files, everything.
 /// i.e. we walk through the source dir.
-/// Ignores inaccessible such files.
+/// Also includes files matched by `[[passthrough_map]]` rules outside the content root.
+/// Ignores inaccessible such files, excluded files, and the output root if it is nested in the content root.
 pub fn source_files(config: &Config) -> impl Iterator<Item = PathBuf> {
-    WalkDir::new(config.content_root())
+    /// Every file under `root`, except under the directories in `skip`.
+    fn walk(root: &Path, skip: [PathBuf; 5]) -> impl Iterator<Item = PathBuf> + use<> {
+        WalkDir::new(root)
+            .into_iter()
+            .filter_entry(move |entry| !skip.iter().any(|dir| entry.path() == dir))
+            .filter_map(|e| e.ok())
+            .filter(|entry| entry.metadata().unwrap().is_file())
+            .map(|entry| entry.path().to_path_buf())
+    }
+
+    // our own output and cache may live under the content root.
+    let skip = config.generated_roots();
+    let mapped_files = mapped_source_roots(config)
         .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|entry| entry.metadata().unwrap().is_file())
-        .map(|entry| entry.path().to_path_buf())
+        .flat_map({
+            let skip = skip.clone();
+            move |root| walk(&root, skip.clone())
+        })
+        .filter(|path| {
+            config
+                .passthrough_copy_globs
+                .mapped_destination(path)
+                .is_some()
+        });
+
+    walk(&config.content_root(), skip)
+        .chain(mapped_files)
+        .filter(|path| !config.exclude_globs.is_excluded(path))
+}
+
+/// The directories `[[passthrough_map]]` rules copy from that aren't already under the content root or each other.
+pub fn mapped_source_roots(config: &Config) -> Vec<PathBuf> {
+    let content_root = config.content_root();
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for map in config.passthrough_copy_globs.maps() {
+        if map.base.starts_with(&content_root)
+            || roots.iter().any(|root| map.base.starts_with(root))
+        {
+            continue;
+        }
+        roots.retain(|root| !root.starts_with(&map.base));
+        roots.push(map.base.clone());
+    }
+    roots
 }
 
 pub enum CompileOutput {
@@ -34,7 +77,42 @@ pub enum CompileOutput {
 }
 
 impl CompileOutput {
+    /// A name for the variant, e.g. for reports.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Noop => "noop",
+            Self::Passthrough(_) => "passthrough",
+            Self::RecompileAll => "recompile-all",
+            Self::CompileToPath(_) => "compile",
+        }
+    }
+
+    /// Where the source file is written to, if anywhere.
+    pub fn dst_path(&self) -> Option<&Path> {
+        match self {
+            Self::Passthrough(dst_path) | Self::CompileToPath(dst_path) => Some(dst_path),
+            Self::Noop | Self::RecompileAll => None,
+        }
+    }
+
     pub fn from_full_path(full_path: &Path, config: &Config) -> Result<Self> {
+        if config.exclude_globs.is_excluded(full_path) {
+            log::trace!(
+                "CompileOutput::from_full_path({:?}, config) computed Noop for excluded file",
+                full_path
+            );
+            return Ok(Self::Noop);
+        }
+
+        if let Some(dst_path) = config.passthrough_copy_globs.mapped_destination(full_path) {
+            log::trace!(
+                "CompileOutput::from_full_path({:?}, config) computed mapped Passthrough to {:?}",
+                full_path,
+                dst_path
+            );
+            return Ok(Self::Passthrough(dst_path));
+        }
+
         if config.passthrough_copy_globs.matches_path_with(&full_path) {
             let rel_path = full_path.strip_prefix(&config.content_root())?;
             let dst_path = config.output_root().join(rel_path);
@@ -54,6 +132,14 @@ impl CompileOutput {
             return Ok(Self::Noop);
         }
 
+        if config.is_partial(full_path) {
+            log::trace!(
+                "CompileOutput::from_full_path({:?}, config) computed Noop for partial",
+                full_path
+            );
+            return Ok(Self::Noop);
+        }
+
         if let Ok(_) = full_path.strip_prefix(config.template_root()) {
             log::trace!(
                 "CompileOutput::from_full_path({:?}, config) computed RecompileAll",
@@ -64,7 +150,7 @@ impl CompileOutput {
             let rel_parent = path_to_typ_in_src.parent().context("Found no parent.")?;
             let parent_dir_in_dst = config.output_root().join(rel_parent);
             let file_in_dst = if full_path.file_name().context("Found no file name")? == "index.typ"
-                || config.literal_paths
+                || config.file_settings(full_path).literal_paths
             {
                 let mut file_in_dst =
                     parent_dir_in_dst.join(full_path.file_name().context("Found no file name.")?);
@@ -84,10 +170,90 @@ impl CompileOutput {
             return Ok(Self::CompileToPath(file_in_dst));
         }
 
-        unreachable!(
-            "Should not be reachable: all files passed into here should be in src or templates..."
-        )
+        // e.g. a Typst file outside src and templates that pages import.
+        log::trace!(
+            "CompileOutput::from_full_path({:?}, config) computed Noop outside src and templates",
+            full_path
+        );
+        Ok(Self::Noop)
+    }
+}
+
+/// `--input` arguments for the page's [`FileSettings::inputs`] plus ones describing the page at `path`, which compiles to `dst_path`:
+///
+/// - `cts-source-path`: the source file, relative to the content root.
+/// - `cts-output-path`: the output file, relative to the output root.
+/// - `cts-url`: the absolute URL the page is served at, e.g. `/blog/post/`.
+/// - `cts-build-time`: when the page was compiled, in RFC 3339 format, e.g. `2025-12-02T09:30:00Z`.
+pub fn page_input_args(
+    path: &Path,
+    dst_path: &Path,
+    settings: &FileSettings,
+    config: &Config,
+) -> Result<Vec<String>> {
+    let source_path = path.strip_prefix(config.content_root())?;
+    let output_path = dst_path.strip_prefix(config.output_root())?;
+
+    let mut args = settings.typst_input_args();
+    for (key, value) in [
+        ("cts-source-path", slash_path(source_path)),
+        ("cts-output-path", slash_path(output_path)),
+        ("cts-url", page_url(dst_path, config)?),
+        ("cts-build-time", rfc3339_now()),
+    ] {
+        args.push("--input".to_string());
+        args.push(format!("{key}={value}"));
     }
+
+    Ok(args)
+}
+
+/// The absolute URL the page compiled to `dst_path` is served at, e.g. `/blog/post/`.
+pub fn page_url(dst_path: &Path, config: &Config) -> Result<String> {
+    let output_path = dst_path.strip_prefix(config.output_root())?;
+    let url = if output_path.file_name() == Some(OsStr::new("index.html")) {
+        match output_path.parent().map(slash_path).as_deref() {
+            None | Some("") => "/".to_string(),
+            Some(parent) => format!("/{parent}/"),
+        }
+    } else {
+        format!("/{}", slash_path(output_path))
+    };
+    Ok(url)
+}
+
+/// Join path components with `/`, no matter the platform.
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The current UTC time formatted like `2025-12-02T09:30:00Z`.
+pub fn rfc3339_now() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
 }
 
 /// Return all files as a json object.
@@ -100,75 +266,70 @@ impl CompileOutput {
 pub fn files_as_json(config: &Config) -> Result<String> {
     let mut json = JsonValue::new_object();
 
-    let (tx, rx) = mpsc::channel::<(String, JsonValue)>();
-
     let source_files: Vec<PathBuf> = source_files(&config).collect();
-    let num_messages = source_files.len();
-
-    std::thread::scope(|s| -> Result<()> {
-        for file in source_files {
-            let tx = tx.clone();
-
-            s.spawn(move || -> Result<()> {
-                let key = file.to_string_lossy().to_string();
-                let mut value = JsonValue::new_array();
-
-                if let (FileListing::IncludeData, CompileOutput::CompileToPath(_)) = (
-                    &config.file_listing,
-                    CompileOutput::from_full_path(&file, &config)?,
-                ) {
-                    let args = [
-                        OsStr::new("--color"),
-                        OsStr::new(config.color.str_collapsing_auto()),
-                        OsStr::new("query"),
-                        OsStr::new(&file),
-                        OsStr::new("<data>"),
-                        OsStr::new("--features"),
-                        OsStr::new("html"),
-                        OsStr::new("--root"),
-                        OsStr::new(&config.project_root),
-                    ];
-
-                    let mut query_output = Command::new("typst")
-                        .args(args)
-                        .args(&config.compilation_extra_args)
-                        .output()
-                        .context(anyhow!(
-                            "Failed to query <data> in the file {}. \
-                            Maybe you don't have Typst installed? \
-                            https://typst.app/open-source/#download \
-                            We ran `typst` with args, extra args: {:?} {:?}",
-                            &file.to_string_lossy(),
-                            args,
-                            &config.compilation_extra_args
-                        ))?;
-
-                    if !query_output.stderr.is_empty() {
-                        log::warn!(
-                            target: "typst query stderr",
-                            "{}",
-                            String::from_utf8(std::mem::take(&mut query_output.stderr))?
-                        );
-                    }
-
-                    if query_output.status.success() {
-                        value = json::parse(str::from_utf8(&query_output.stdout)?)?;
-                    } else {
-                        log::info!("failed to query {}", &file.to_string_lossy());
-                    }
-                }
 
-                tx.send((key, value))?;
+    let entries = jobs::run_bounded(source_files, config.jobs, |file| -> Result<_> {
+        let key = file.to_string_lossy().to_string();
+        let mut value = JsonValue::new_array();
+
+        if let (FileListing::IncludeData, CompileOutput::CompileToPath(dst_path)) = (
+            &config.file_listing,
+            CompileOutput::from_full_path(&file, &config)?,
+        ) {
+            let args = [
+                OsStr::new("--color"),
+                OsStr::new(config.color.str_collapsing_auto()),
+                OsStr::new("query"),
+                OsStr::new(&file),
+                OsStr::new("<data>"),
+                OsStr::new("--features"),
+                OsStr::new("html"),
+                OsStr::new("--root"),
+                OsStr::new(&config.project_root),
+                OsStr::new("--diagnostic-format"),
+                OsStr::new("short"),
+            ];
+
+            let settings = config.file_settings(&file);
+            let input_args = page_input_args(&file, &dst_path, &settings, config)?;
+
+            let started = Instant::now();
+            let mut query_output = Command::new("typst")
+                .args(args)
+                .args(&input_args)
+                .args(settings.compilation_extra_args)
+                .output()
+                .context(anyhow!(
+                    "Failed to query <data> in the file {}. \
+                    Maybe you don't have Typst installed? \
+                    https://typst.app/open-source/#download \
+                    We ran `typst` with args, input args, extra args: {:?} {:?} {:?}",
+                    &file.to_string_lossy(),
+                    args,
+                    input_args,
+                    settings.compilation_extra_args
+                ))?;
+            timings::record(timings::Kind::Query, &file, started, Instant::now());
+
+            diagnostics::log_stderr(
+                "typst query",
+                &file,
+                &String::from_utf8(std::mem::take(&mut query_output.stderr))?,
+                config,
+            );
 
-                Ok(())
-            });
+            if query_output.status.success() {
+                value = json::parse(str::from_utf8(&query_output.stdout)?)?;
+            } else {
+                log::info!("failed to query {}", &file.to_string_lossy());
+            }
         }
 
-        Ok(())
-    })?;
+        Ok((key, value))
+    });
 
-    for _ in 0..num_messages {
-        let (key, value) = rx.recv()?;
+    for entry in entries {
+        let (key, value) = entry?;
         json[key] = value;
     }
 
@@ -178,7 +339,35 @@ pub fn files_as_json(config: &Config) -> Result<String> {
 pub fn compile_from_scratch(config: &Config) -> Result<()> {
     let start = Instant::now();
 
+    report::start(config);
+    timings::start(config);
+    let built = prepare(config).and_then(|()| {
+        if config.staged_builds {
+            compile_staged(config)
+        } else {
+            compile_everything(config)
+        }
+    });
+    // e.g. from querying the file listing, if compiling never started.
+    diagnostics::log_warnings(config);
+    let reported = report::finish(config, built.is_ok());
+    let timed = timings::finish(config, built.is_ok());
+    built?;
+    reported?;
+    timed?;
+
+    log::info!(
+        "compiled project from scratch in {}s",
+        Instant::now().duration_since(start).as_millis() as f32 / 1000.0
+    );
+
+    Ok(())
+}
+
+/// Run the init command and write the file listing, which pages may read.
+fn prepare(config: &Config) -> Result<()> {
     if config.init.len() > 0 {
+        let start = Instant::now();
         log::info!("running init command");
         let mut init_output = Command::new(&config.init[0])
             .args(&config.init[1..])
@@ -203,43 +392,212 @@ pub fn compile_from_scratch(config: &Config) -> Result<()> {
             ));
         }
         log::trace!("finished init");
+        report::step("init", start.elapsed());
+        timings::record(
+            timings::Kind::Init,
+            Path::new(&config.init[0]),
+            start,
+            Instant::now(),
+        );
     }
 
     if let FileListing::Disabled = config.file_listing {
         log::trace!("not file listing");
     } else {
-        let listing_path = config.project_root.join("files.json");
+        let listing_path = config.file_listing_path();
         log::info!(
             "generating and writing file listing to {}",
             listing_path.to_string_lossy()
         );
-        fs::write(&listing_path, &files_as_json(&config)?)?;
+        let start = Instant::now();
+        atomic::write(&listing_path, files_as_json(&config)?)?;
+        report::step("file_listing", start.elapsed());
     }
 
+    Ok(())
+}
+
+/// Compile and copy every source file.
+fn compile_everything(config: &Config) -> Result<()> {
     log::info!("starting compilation");
-    compile_batch(source_files(&config), &config)?;
+    let start = Instant::now();
+    let compiled = compile_batch(source_files(&config), &config);
+    report::step("compile", start.elapsed());
+    // every page has now been compiled, successfully or not, so we know what each one reads.
+    deps::mark_complete();
+    if config.prune_stale {
+        remove_stale_outputs(config)?;
+    }
+    compiled
+}
+
+/// Compile everything into [`Config::staging_root`], starting from the current output,
+/// then swap it in for the output root if nothing failed.
+fn compile_staged(config: &Config) -> Result<()> {
+    let output_root = config.output_root();
+    let staging_root = config.staging_root();
+    let replaced_root = config.replaced_output_root();
+
+    // left behind if a previous build was interrupted.
+    for root in [&staging_root, &replaced_root] {
+        if root.exists() {
+            fs::remove_dir_all(root)?;
+        }
+    }
+    // so files we don't produce, and pages the cache skips, carry over.
+    if output_root.exists() {
+        atomic::link_dir(&output_root, &staging_root)?;
+    } else {
+        fs::create_dir_all(&staging_root)?;
+    }
+
+    log::info!("staging build in {}", staging_root.to_string_lossy());
+    if let Err(e) = compile_everything(&config.staged()?) {
+        let _dont_validate_removal = fs::remove_dir_all(&staging_root);
+        return Err(e.context(format!(
+            "Kept the previous build in {}, since this one failed.",
+            output_root.to_string_lossy()
+        )));
+    }
 
+    // the output root is briefly missing between these renames.
+    if output_root.exists() {
+        fs::rename(&output_root, &replaced_root)?;
+    }
+    fs::rename(&staging_root, &output_root).context(format!(
+        "Failed to move the staged build into {}",
+        output_root.to_string_lossy()
+    ))?;
+    if replaced_root.exists() {
+        fs::remove_dir_all(&replaced_root)?;
+    }
+
+    Ok(())
+}
+
+/// Delete the output of `path`, e.g. because it was deleted or renamed.
+///
+/// If `path` was a directory, delete what no other source produces from where its outputs would be.
+pub fn remove_output(path: &Path, config: &Config) -> Result<()> {
+    deps::forget(path);
+    cache::forget(path);
+    diagnostics::forget(path);
+
+    let dst_path = match CompileOutput::from_full_path(path, config)? {
+        CompileOutput::CompileToPath(dst_path) | CompileOutput::Passthrough(dst_path) => dst_path,
+        // a deleted directory has no extension, so it looks like a file we ignore.
+        CompileOutput::Noop | CompileOutput::RecompileAll => {
+            match path.strip_prefix(config.content_root()) {
+                Ok(rel_path) => config.output_root().join(rel_path),
+                Err(_) => return Ok(()),
+            }
+        }
+    };
+    if dst_path.is_dir() {
+        if config.output_root_holds_sources() {
+            log::warn!(
+                "not removing the outputs of {}, since the output directory contains sources",
+                path.to_string_lossy()
+            );
+            return Ok(());
+        }
+        return remove_stale_outputs_in(&dst_path, config);
+    }
+    if !dst_path.exists() {
+        return Ok(());
+    }
+
+    fs::remove_file(&dst_path).context(format!("Failed to remove output {:?}", &dst_path))?;
     log::info!(
-        "compiled project from scratch in {}s",
-        Instant::now().duration_since(start).as_millis() as f32 / 1000.0
+        "removed output {} of {}",
+        dst_path.to_string_lossy(),
+        path.to_string_lossy()
     );
+    // pages compile to DIR/index.html, so don't leave DIR behind.
+    remove_empty_parents(&dst_path, &config.output_root())
+}
+
+/// Delete the directories containing `path` that are empty, up to but not including `output_root`.
+fn remove_empty_parents(path: &Path, output_root: &Path) -> Result<()> {
+    for dir in path.ancestors().skip(1) {
+        if dir == output_root
+            || !dir.starts_with(output_root)
+            || fs::read_dir(dir)?.next().is_some()
+        {
+            break;
+        }
+        fs::remove_dir(dir)?;
+    }
+
+    Ok(())
+}
+
+/// Delete files in the output root that no source file compiles or copies to anymore, except those matching `preserve`.
+pub fn remove_stale_outputs(config: &Config) -> Result<()> {
+    remove_stale_outputs_in(&config.output_root(), config)
+}
+
+/// [`remove_stale_outputs`], but only under `dir` in the output root.
+fn remove_stale_outputs_in(dir: &Path, config: &Config) -> Result<()> {
+    // `Config` made sure the output root doesn't contain any sources.
+    let output_root = config.output_root();
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    let mut produced = BTreeSet::new();
+    for path in source_files(config) {
+        match CompileOutput::from_full_path(&path, config)? {
+            CompileOutput::CompileToPath(dst_path) | CompileOutput::Passthrough(dst_path) => {
+                produced.insert(dst_path);
+            }
+            CompileOutput::Noop | CompileOutput::RecompileAll => (),
+        }
+    }
+
+    let stale: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| !produced.contains(path) && !config.is_preserved(path))
+        .collect();
+    for path in &stale {
+        fs::remove_file(path).context(format!("Failed to remove stale output {path:?}"))?;
+        log::info!("removed stale output {}", path.to_string_lossy());
+        remove_empty_parents(path, &output_root)?;
+    }
 
     Ok(())
 }
 
-pub fn compile_single(path: &Path, config: &Config) -> Result<()> {
+/// What [`compile_single`] did with a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compiled {
+    /// Compiled or copied it, or found nothing to do.
+    Done,
+    /// It can affect every page, so everything must be compiled again. That's left to the caller, so it doesn't run
+    /// inside the job pool, and start more than `jobs` processes.
+    NeedsFullBuild,
+}
+
+pub fn compile_single(path: &Path, config: &Config) -> Result<Compiled> {
     log::trace!("here1 compiling {}", path.to_string_lossy());
 
-    match CompileOutput::from_full_path(path, config)? {
+    // whatever went wrong last time, only this compilation matters now.
+    diagnostics::forget(path);
+    let compile_output = CompileOutput::from_full_path(path, config)?;
+    report::update(path, |file| {
+        file.kind = compile_output.kind();
+        file.output = compile_output
+            .dst_path()
+            .and_then(|dst_path| dst_path.strip_prefix(config.output_root()).ok())
+            .map(Path::to_path_buf);
+    });
+
+    match compile_output {
         CompileOutput::Noop => (),
-        CompileOutput::RecompileAll => {
-            compile_from_scratch(config)?
-            // need to be careful of infinite recursion, compile_everything calls us (compile)
-            // should be fine because this code path should only trigger when compiling
-            // on the template root.
-            //
-            // ... what if someone puts their template code in their src folder?
-        }
+        CompileOutput::RecompileAll => return Ok(Compiled::NeedsFullBuild),
         CompileOutput::Passthrough(dst_path) => {
             fs::create_dir_all(
                 &dst_path
@@ -247,8 +605,9 @@ pub fn compile_single(path: &Path, config: &Config) -> Result<()> {
                     .context(anyhow!("Couldn't find parent."))?,
             )?;
 
-            fs::copy(path, &dst_path)
-                .context(format!("Failed to write output to {:?}", &dst_path))?;
+            atomic::copy(path, &dst_path)?;
+            let output_bytes = fs::metadata(&dst_path).map(|metadata| metadata.len()).ok();
+            report::update(path, |file| file.output_bytes = output_bytes);
 
             log::trace!(
                 "passthroughcopied {} to {}",
@@ -258,6 +617,53 @@ pub fn compile_single(path: &Path, config: &Config) -> Result<()> {
         }
         CompileOutput::CompileToPath(dst_path) => {
             log::trace!("compile_single:t10");
+            let settings = config.file_settings(path);
+            let input_args = page_input_args(path, &dst_path, &settings, config)?;
+
+            // the build time always changes, and a cached page keeps the one it was built with.
+            // the output is relative to the output root, which differs for staged builds.
+            let post_processing_files = cache::command_file_hashes(settings.post_processing_typ);
+            let fingerprint = cache::fingerprint(&[
+                &[
+                    dst_path.strip_prefix(config.output_root())?.as_os_str(),
+                    config.project_root.as_os_str(),
+                ],
+                &input_args
+                    .iter()
+                    .filter(|arg| !arg.starts_with("cts-build-time="))
+                    .map(OsStr::new)
+                    .collect::<Vec<_>>(),
+                &settings
+                    .compilation_extra_args
+                    .iter()
+                    .map(OsStr::new)
+                    .collect::<Vec<_>>(),
+                &settings
+                    .post_processing_typ
+                    .iter()
+                    .map(OsStr::new)
+                    .collect::<Vec<_>>(),
+                &post_processing_files
+                    .iter()
+                    .map(OsStr::new)
+                    .collect::<Vec<_>>(),
+            ]);
+            if let Some(page_deps) = cache::lookup(path, &fingerprint, &dst_path) {
+                log::debug!(
+                    "{} is unchanged since it was cached",
+                    path.to_string_lossy()
+                );
+                deps::record(path, page_deps);
+                let output_bytes = fs::metadata(&dst_path).map(|metadata| metadata.len()).ok();
+                report::update(path, |file| {
+                    file.status = Some("cached");
+                    file.output_bytes = output_bytes;
+                });
+                return Ok(Compiled::Done);
+            }
+
+            let deps_file = deps::deps_file();
+            let started = Instant::now();
             let mut child = {
                 let args = [
                     OsStr::new("--color"),
@@ -271,18 +677,26 @@ pub fn compile_single(path: &Path, config: &Config) -> Result<()> {
                     OsStr::new("html"),
                     OsStr::new("--root"),
                     OsStr::new(&config.project_root),
+                    OsStr::new("--deps"),
+                    OsStr::new(&deps_file),
+                    OsStr::new("--deps-format"),
+                    OsStr::new("json"),
+                    OsStr::new("--diagnostic-format"),
+                    OsStr::new("short"),
                 ];
                 log::trace!("compile_single:t11");
                 log::trace!(
-                    "compile_single:path {:?}, trying to run typst with args, extra args: {:?} {:?}",
+                    "compile_single:path {:?}, trying to run typst with args, input args, extra args: {:?} {:?} {:?}",
                     &path,
                     args,
-                    &config.compilation_extra_args
+                    input_args,
+                    settings.compilation_extra_args
                 );
 
                 Command::new("typst")
                     .args(args)
-                    .args(&config.compilation_extra_args)
+                    .args(&input_args)
+                    .args(settings.compilation_extra_args)
                     .stdout(Stdio::piped())
                     .stderr(Stdio::piped())
                     .spawn()
@@ -290,9 +704,10 @@ pub fn compile_single(path: &Path, config: &Config) -> Result<()> {
                         "Failed to run Typst compiler. \
                         Maybe you don't have it installed? \
                         https://typst.app/open-source/#download \
-                        We ran `typst` with args, extra args: {:?} {:?}",
+                        We ran `typst` with args, input args, extra args: {:?} {:?} {:?}",
                         args,
-                        &config.compilation_extra_args
+                        input_args,
+                        settings.compilation_extra_args
                     ))?
             };
 
@@ -300,7 +715,7 @@ pub fn compile_single(path: &Path, config: &Config) -> Result<()> {
                 .stderr
                 .take()
                 .expect("specified Stdio::piped() for the child");
-            std::thread::spawn(move || {
+            let compile_stderr_thread = std::thread::spawn(move || {
                 let mut compile_stderr_string = String::new();
                 compile_stderr
                     .read_to_string(&mut compile_stderr_string)
@@ -308,23 +723,24 @@ pub fn compile_single(path: &Path, config: &Config) -> Result<()> {
                         log::error!("Typst stderr wasn't valid UTF-8.");
                         0 // dummy number to type check
                     });
-
-                if !compile_stderr_string.is_empty() {
-                    log::warn!(target: "typst compile stderr", "{compile_stderr_string}");
-                }
+                compile_stderr_string
             });
 
-            if config.post_processing_typ.len() > 0 {
-                child = Command::new(&config.post_processing_typ[0])
-                    .args(&config.post_processing_typ[1..])
-                    .stdin(child.stdout.context("Found no child")?)
+            // with post-processing, `child` becomes the post-processor, so keep Typst around to check it too.
+            let mut typst_child = None;
+            if !settings.post_processing_typ.is_empty() {
+                let typst_stdout = child.stdout.take().context("Found no child")?;
+                let pproc_child = Command::new(&settings.post_processing_typ[0])
+                    .args(&settings.post_processing_typ[1..])
+                    .stdin(typst_stdout)
                     .stdout(Stdio::piped())
                     .stderr(Stdio::piped())
                     .spawn()
                     .context(anyhow!(
                         "Failed to post process. We tried to run the command {:?}",
-                        &config.post_processing_typ
+                        settings.post_processing_typ
                     ))?;
+                typst_child = Some(std::mem::replace(&mut child, pproc_child));
 
                 let mut pproc_stderr = child
                     .stderr
@@ -347,21 +763,86 @@ pub fn compile_single(path: &Path, config: &Config) -> Result<()> {
 
             log::trace!("compile_single:t14");
 
+            // wait for Typst in the background while post-processing, to know when each finished.
+            let typst_waiter = typst_child.map(|mut typst_child| {
+                std::thread::spawn(move || (typst_child.wait(), Instant::now()))
+            });
             let output = child
                 .wait_with_output()
                 .context("Waiting for output of typst and post-processing failed.")?;
+            let finished = Instant::now();
+            let post_processed = typst_waiter.is_some();
+            let (typst_succeeded, typst_finished) = match typst_waiter {
+                Some(typst_waiter) => {
+                    let (status, typst_finished) =
+                        typst_waiter.join().expect("waiting doesn't panic");
+                    // the waiting thread may only notice after the post-processor exits, just after Typst.
+                    (
+                        status.context("Waiting for typst failed.")?.success(),
+                        typst_finished.min(finished),
+                    )
+                }
+                None => (output.status.success(), finished),
+            };
+            let typst_duration = typst_finished.duration_since(started);
+            let post_processing_duration = post_processed.then(|| finished - typst_finished);
+            timings::record(timings::Kind::Compile, path, started, typst_finished);
+            if post_processed {
+                timings::record(
+                    timings::Kind::PostProcessing,
+                    path,
+                    typst_finished,
+                    finished,
+                );
+            }
 
             log::trace!("compile_single:t15");
 
-            if !output.status.success() {
-                return Err(anyhow!("Compiling {} failed.", path.to_string_lossy()));
+            // Typst has exited, so this won't block for long.
+            let diagnostics = diagnostics::log_stderr(
+                "typst compile",
+                path,
+                &compile_stderr_thread.join().unwrap_or_default(),
+                config,
+            );
+            diagnostics::record(path, diagnostics.clone());
+            report::update(path, |file| {
+                file.typst = Some(typst_duration);
+                file.post_processing = post_processing_duration;
+                file.add_diagnostics(&diagnostics);
+            });
+
+            if !(typst_succeeded && output.status.success()) {
+                deps::record_unknown(path);
+                cache::forget(path);
+                let _dont_validate_removal = fs::remove_file(&deps_file);
+                return Err(CompileFailure {
+                    path: path.to_path_buf(),
+                    diagnostics,
+                }
+                .into());
             }
 
+            let page_deps = deps::read_deps_file(&deps_file);
+            let output_bytes = output.stdout.len() as u64;
+            report::update(path, |file| file.output_bytes = Some(output_bytes));
+
             log::trace!("compile_single:t16");
 
             fs::create_dir_all(&dst_path.parent().context("Found no parent.")?)?;
-            fs::write(&dst_path, output.stdout)
-                .context(format!("Failed to write output to {:?}", &dst_path))?;
+            atomic::write(&dst_path, output.stdout)?;
+
+            match page_deps {
+                Ok(page_deps) => {
+                    cache::store(path, fingerprint, &page_deps);
+                    deps::record(path, page_deps);
+                }
+                Err(e) => {
+                    log::debug!("{e:?}");
+                    deps::record_unknown(path);
+                    cache::forget(path);
+                }
+            }
 
             log::trace!(
                 "typfile compiled {} to {}",
@@ -371,32 +852,132 @@ pub fn compile_single(path: &Path, config: &Config) -> Result<()> {
         }
     };
 
-    Ok(())
+    Ok(Compiled::Done)
+}
+
+/// Compiling a source file failed, with what Typst reported.
+#[derive(Debug)]
+pub struct CompileFailure {
+    pub path: PathBuf,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Display for CompileFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Compiling {} failed.", self.path.to_string_lossy())
+    }
+}
+
+impl std::error::Error for CompileFailure {}
+
+/// A table of each file in `failures` and the first error it had.
+fn failure_summary(failures: &[(PathBuf, anyhow::Error)], config: &Config) -> String {
+    let rows: Vec<(String, String)> = failures
+        .iter()
+        .map(|(path, e)| {
+            let path = path.strip_prefix(&config.project_root).unwrap_or(path);
+            let reason = e
+                .downcast_ref::<CompileFailure>()
+                .and_then(|failure| {
+                    failure
+                        .diagnostics
+                        .iter()
+                        .find(|diagnostic| diagnostic.severity == Severity::Error)
+                })
+                .map_or_else(|| e.to_string(), |diagnostic| diagnostic.to_string());
+            (path.to_string_lossy().to_string(), reason)
+        })
+        .collect();
+    let width = rows.iter().map(|(path, _)| path.len()).max().unwrap_or(0);
+
+    let mut summary = format!("{} files failed to compile:", rows.len());
+    for (path, reason) in rows {
+        summary += &format!("\n  {path:width$}  {reason}");
+    }
+    summary
 }
 
 /// Blocks until batch of paths are compiled.
 ///
-/// Each path is compiled under a separate thread. Paths can be anywhere under src or templates.
+/// Paths are compiled on the job pool, at most `jobs` at a time. Paths can be anywhere under src or templates.
 /// Calling this function on paths outside those folders mayyy cause errors.
 pub fn compile_batch(paths: impl Iterator<Item = PathBuf>, config: &Config) -> Result<()> {
     let start = Instant::now();
 
-    std::thread::scope(|s| -> Result<()> {
-        let mut paths_and_handles = vec![];
-        for path in paths {
-            paths_and_handles.push((
-                path.clone(),
-                s.spawn(move || -> Result<()> { compile_single(&path, &config) }),
+    let results = jobs::run_bounded(paths.collect(), config.jobs, |path| {
+        log::debug!("trying to compile {}", path.to_str().unwrap());
+        let result = compile_single(&path, config);
+        (path, result)
+    });
+    // keep what compiled successfully even if something else failed.
+    cache::save().unwrap_or_else(|e| log::warn!("{:?}", e));
+    diagnostics::log_warnings(config);
+
+    if results
+        .iter()
+        .any(|(_, result)| matches!(result, Ok(Compiled::NeedsFullBuild)))
+    {
+        // need to be careful of infinite recursion, compile_from_scratch calls us.
+        // should be fine because only templates need a full build, and those aren't compiled on their own.
+        //
+        // ... what if someone puts their template code in their src folder?
+        log::info!("a changed file affects every page, so recompiling everything");
+        return compile_from_scratch(config);
+    }
+
+    let total = results.len();
+    let mut failures = Vec::new();
+    for (path, result) in results {
+        match result {
+            Ok(_) => report::update(&path, |file| {
+                file.status.get_or_insert("ok");
+            }),
+            Err(e) => {
+                // e.g. the post-processor failing, so the error is shown while serving.
+                if diagnostics::of(&path)
+                    .iter()
+                    .all(|diagnostic| diagnostic.severity != Severity::Error)
+                {
+                    diagnostics::add(
+                        &path,
+                        Diagnostic {
+                            severity: Severity::Error,
+                            file: None,
+                            line: None,
+                            column: None,
+                            message: format!("{e:#}"),
+                            hints: Vec::new(),
+                        },
+                    );
+                }
+                report::update(&path, |file| {
+                    file.status = Some("failed");
+                    // e.g. the post-processor failing, which Typst had nothing to say about.
+                    if file.errors.is_empty() {
+                        file.errors.push(format!("{e:#}"));
+                    }
+                });
+                failures.push((path, e));
+            }
+        }
+    }
+    if !failures.is_empty() {
+        if config.keep_going {
+            log::error!("{}", failure_summary(&failures, config));
+            return Err(anyhow!(
+                "{} of {total} files failed to compile.",
+                failures.len()
             ));
         }
-
-        for (path, handle) in paths_and_handles {
-            log::debug!("trying to compile {}", path.to_str().unwrap());
-            handle.join().unwrap()?;
+        let others = failures.len() - 1;
+        let (_, first_failure) = failures.swap_remove(0);
+        if others == 0 {
+            return Err(first_failure);
         }
-
-        Ok(())
-    })?;
+        return Err(first_failure.context(format!(
+            "{others} other files failed to compile too. Pass --keep-going to see every failure."
+        )));
+    }
 
     log::info!(
         "compiled batch of files in {}s",
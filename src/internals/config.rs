@@ -4,14 +4,17 @@ use anyhow::{Context as _, Result, anyhow};
 use glob::{MatchOptions, Pattern};
 use nanoserde::{Toml, TomlParser};
 use onlyargs_derive::OnlyArgs;
-use std::fmt::Debug;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt::{Debug, Display};
 use std::fs;
 use std::io::IsTerminal as _;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 use std::sync::OnceLock;
+use walkdir::WalkDir;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum LogWithColor {
     Auto,
     Always,
@@ -78,29 +81,228 @@ struct Args {
     verbose: bool,
     /// Enable very verbose logging.
     trace: bool,
+    /// Compile every page, neither reading nor updating the build cache.
+    #[long]
+    no_cache: bool,
+    /// After building, delete output files no source produces anymore, except those matching `preserve`.
+    #[long]
+    prune_stale: bool,
+    /// Build the whole site next to the output directory, and only replace it if the build succeeds.
+    #[long]
+    staged_builds: bool,
+    /// Summarize every file that failed to compile, instead of only the first. [default: on when watching or serving]
+    #[long]
+    keep_going: bool,
+    /// Write an HTML chart of when each Typst and post-processing process ran during the build to `cts-timings`, next to the output directory.
+    #[long]
+    timings: bool,
     /// Whether to use color. [default: auto] [possible values: auto, always, never]
     color: Option<String>,
+    /// Pass KEY=VALUE to Typst as `sys.inputs`. Can be used multiple times. Overrides `[inputs]`.
+    #[long]
+    input: Vec<String>,
+    /// Use the specified directory, relative to the project root, as the content root. Overrides `content_dir`.
+    #[long]
+    src: Option<PathBuf>,
+    /// Use the specified directory, relative to the project root, as the output root. Overrides `output_dir`.
+    #[long]
+    out: Option<PathBuf>,
+    /// Apply the `[profile.NAME]` section of the config file. [default: dev when watching or serving]
+    #[long]
+    profile: Option<String>,
+    /// Run at most this many Typst processes at once. Overrides `jobs`. [default: available parallelism]
+    jobs: Option<usize>,
+    /// Write a JSON report of what each full build did with each source file, and how long it took, to the specified path.
+    #[long]
+    report: Option<PathBuf>,
+    /// With `new`, copy the specified project directory instead of the built-in scaffold.
+    #[long]
+    from: Option<PathBuf>,
+    /// With `config`, the format to print in. [default: toml] [possible values: toml, json]
+    #[long]
+    format: Option<String>,
+    /// What to do. [default: build]
+    /// build:             Build the site at the project root.
+    /// new [DIR]:         Create a new project in DIR. [default: --path, or else the current directory]
+    /// config:            Print the resolved configuration and where each value came from.
+    /// clean:             Delete the build cache.
+    /// completions SHELL: Print a completion script for SHELL. [possible values: bash, zsh, fish]
+    /// man:               Print a man page.
+    #[positional]
+    command: Vec<String>,
+}
+
+/// What compile-typst-site was asked to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Subcommand {
+    /// Build the site, and possibly watch or serve it.
+    Build,
+    /// Create a new project in `dir`, copying `from` if given.
+    New { dir: PathBuf, from: Option<PathBuf> },
+    /// Print the resolved configuration.
+    Config { format: ConfigFormat },
+    /// Delete the build cache.
+    Clean,
+    /// Print a completion script.
+    Completions { shell: Shell },
+    /// Print a man page.
+    Man,
+}
+
+impl Subcommand {
+    fn parse(
+        command: Vec<String>,
+        path: Option<&Path>,
+        from: Option<PathBuf>,
+        format: Option<String>,
+    ) -> Result<Self> {
+        let mut command = command.into_iter();
+        let name = command.as_slice().first().map(String::as_str);
+        if from.is_some() && name != Some("new") {
+            return Err(anyhow!("--from can only be used with `new`."));
+        }
+        if format.is_some() && name != Some("config") {
+            return Err(anyhow!("--format can only be used with `config`."));
+        }
+
+        let subcommand = match command.next().as_deref() {
+            None | Some("build") => Self::Build,
+            Some("new") => {
+                let dir = match command.next() {
+                    Some(dir) => PathBuf::from(dir),
+                    None => match path {
+                        Some(path) => path.to_path_buf(),
+                        None => std::env::current_dir()?,
+                    },
+                };
+                Self::New { dir, from }
+            }
+            Some("config") => Self::Config {
+                format: match format {
+                    Some(format) => format.parse()?,
+                    None => ConfigFormat::default(),
+                },
+            },
+            Some("completions") => Self::Completions {
+                shell: command
+                    .next()
+                    .context("`completions` needs a shell, one of \"bash\", \"zsh\", or \"fish\".")?
+                    .parse()?,
+            },
+            Some("clean") => Self::Clean,
+            Some("man") => Self::Man,
+            Some(other) => {
+                return Err(anyhow!(
+                    "Unknown command {other:?}. \
+                    Must be one of \"build\", \"new\", \"config\", \"clean\", \"completions\", or \"man\"."
+                ));
+            }
+        };
+
+        if let Some(extra) = command.next() {
+            return Err(anyhow!("Unexpected argument {extra:?}."));
+        }
+
+        Ok(subcommand)
+    }
+}
+
+/// The format `config` prints the resolved configuration in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConfigFormat {
+    #[default]
+    Toml,
+    Json,
+}
+
+impl FromStr for ConfigFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "toml" => Ok(Self::Toml),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!(
+                "format argument must be one of \"toml\" or \"json\""
+            )),
+        }
+    }
 }
 
+/// A shell `completions` can print a script for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            _ => Err(anyhow!(
+                "shell must be one of \"bash\", \"zsh\", or \"fish\", not {s:?}"
+            )),
+        }
+    }
+}
+
+/// Where a resolved configuration value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    ConfigFile,
+    /// The `[profile.NAME]` section with this name.
+    Profile(String),
+    CommandLine,
+}
+
+impl Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::ConfigFile => write!(f, "config file"),
+            Self::Profile(profile) => write!(f, "profile {profile}"),
+            Self::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+/// The `--help` text, from which completions and the man page are generated.
+pub const HELP: &str = <Args as onlyargs::OnlyArgs>::HELP;
+
 #[derive(Default)]
 struct ConfigFile {
     /// Array of globs to match for passthrough-copying.
     ///
+    /// Like every array of strings in this file, variables are interpolated. A `$` must begin one of:
+    /// - $PROJECT_ROOT, $CONTENT_ROOT, $OUTPUT_ROOT, or $TEMPLATE_ROOT, which are replaced with the respective full paths.
+    ///   Braces are optional, e.g. `${PROJECT_ROOT}`.
+    /// - ${env:NAME}, which is replaced with the environment variable NAME. Errors if unset.
+    /// - ${env:NAME:-default}, which is replaced with the environment variable NAME, or "default" if unset.
+    /// - $$, which is replaced with a literal $.
+    ///
+    /// Globs starting with `!` stop matching files instead, e.g. to copy `assets/**` except `assets/**/*.psd`.
+    /// The last glob that matches a file decides whether it is copied.
+    ///
     /// Example in the TOML config file: `passthrough_copy = ["*.css", "*.js", "assets/*"]
     passthrough_copy: Vec<String>,
     /// Command to run before a full rebuild.
     ///
-    /// Strings should not contain $ unless the symbol begins:
-    /// - $PROJECT_ROOT, which is replaced with the path to the project root.
+    /// Variables are interpolated as described in `passthrough_copy`.
     ///
-    /// E.g., `passthrough_copy = ["python", "$PROJECT_ROOT/prebuild.py"]`.
+    /// E.g., `init = ["python", "$PROJECT_ROOT/prebuild.py"]`.
     init: Vec<String>,
     /// Command to run to post-process HTML files generated by Typst.
     ///
     /// Must take in stdin and return via stdout.
     ///
-    /// Strings should not contain $ unless the symbol begins:
-    /// - $PROJECT_ROOT, which is replaced with the path to the project root.
+    /// Variables are interpolated as described in `passthrough_copy`.
     ///
     /// Example in the TOML config file: `post_processing_typ = ["python", "$PROJECT_ROOT/post_processing_script.py"]`.
     post_processing_typ: Vec<String>,
@@ -140,9 +342,201 @@ struct ConfigFile {
     ///
     /// Example in the TOML config file: `disable_incremental = true`
     disable_incremental: bool,
+    /// Directory, relative to the project root, holding the site's content. Defaults to "src".
+    ///
+    /// Example in the TOML config file: `content_dir = "content"`
+    content_dir: Option<PathBuf>,
+    /// Directory, relative to the project root, that the site is written to. Defaults to "_site".
+    ///
+    /// Example in the TOML config file: `output_dir = "public"`
+    output_dir: Option<PathBuf>,
+    /// Directory, relative to the project root, holding templates. Defaults to "templates".
+    ///
+    /// Changes to files in this directory recompile the pages that use them while watching.
+    ///
+    /// Example in the TOML config file: `template_dir = "layouts"`
+    template_dir: Option<PathBuf>,
+    /// Ignore keys in the config file that `compile-typst-site` doesn't know about, instead of erroring.
+    ///
+    /// This can be helpful for sharing a config file with newer versions of `compile-typst-site`.
+    ///
+    /// Example in the TOML config file: `allow_unknown_keys = true`
+    allow_unknown_keys: bool,
+    /// Values passed to every `typst compile` and `typst query` as `sys.inputs`.
+    ///
+    /// Values are interpolated as described in `passthrough_copy`.
+    /// While watching or serving, `cts-mode` is set to "dev" unless given otherwise.
+    ///
+    /// Example in the TOML config file:
+    /// ```toml
+    /// [inputs]
+    /// base_url = "https://example.com"
+    /// site_title = "My Site"
+    /// ```
+    inputs: BTreeMap<String, String>,
+    /// Settings that apply only to source files matching a glob.
+    ///
+    /// Globs are rooted in the content directory, like `passthrough_copy`.
+    /// Each section may set `literal_paths`, `post_processing_typ`, `compilation_extra_args`, and `[inputs]`,
+    /// which replace the top-level settings for matching files. Inputs are merged key by key.
+    /// When several sections match a file, the most specific glob (the one with the most non-wildcard characters) wins,
+    /// with ties going to the section written last.
+    ///
+    /// Example in the TOML config file:
+    /// ```toml
+    /// [[override]]
+    /// glob = "legacy/**"
+    /// literal_paths = true
+    ///
+    /// [[override]]
+    /// glob = "slides/**"
+    /// compilation_extra_args = ["--ignore-system-fonts", "--ppi", "300"]
+    /// ```
+    overrides: Vec<OverrideFile>,
+    /// Array of globs matching files under the content root to pretend don't exist.
+    ///
+    /// Excluded files are neither compiled, passthrough-copied, listed, nor watched.
+    /// Globs are rooted in the content directory, like `passthrough_copy`.
+    ///
+    /// Example in the TOML config file: `exclude = ["**/README.typ", "vendor/**", "scratch/**"]`
+    exclude: Vec<String>,
+    /// Also exclude files matching patterns in `.gitignore` and `.ctsignore` files.
+    ///
+    /// Ignore files are read from the project root and every directory under the content root.
+    /// Patterns follow `.gitignore` syntax, including `!` to re-include a file.
+    ///
+    /// Example in the TOML config file: `use_ignore_files = true`
+    use_ignore_files: bool,
+    /// Typst files under the content root whose name, or the name of any parent directory, starts with this prefix
+    /// are partials: they can be imported by pages, but aren't compiled to pages themselves.
+    /// Changes to partials recompile the pages that import them while watching.
+    ///
+    /// Defaults to "_". Set to false to treat every Typst file as a page.
+    ///
+    /// Example in the TOML config file: `partial_prefix = "partial-"`
+    partial_prefix: Option<String>,
+    /// Rules to copy files from anywhere to anywhere in the output directory.
+    ///
+    /// Unlike `passthrough_copy`, `from` is a glob rooted in the project root, so it can match files outside the content directory.
+    /// Matched files are copied into the `to` directory, relative to the output root,
+    /// keeping their paths relative to the non-wildcard start of `from`.
+    /// If `from` has no wildcards and `to` doesn't end in `/`, the file is copied to exactly `to` instead.
+    /// Variables are interpolated as described in `passthrough_copy`. The first matching rule wins, before `passthrough_copy`.
+    ///
+    /// Example in the TOML config file:
+    /// ```toml
+    /// [[passthrough_map]]
+    /// from = "node_modules/katex/dist/**"
+    /// to = "vendor/katex/"
+    ///
+    /// [[passthrough_map]]
+    /// from = "../shared-assets/*"
+    /// to = "assets/"
+    /// ```
+    passthrough_map: Vec<PassthroughMapFile>,
+    /// Match `passthrough_copy` and `[[passthrough_map]]` globs case-insensitively.
+    ///
+    /// Example in the TOML config file: `passthrough_ignore_case = true`
+    passthrough_ignore_case: bool,
+    /// Don't let wildcards in `passthrough_copy` and `[[passthrough_map]]` globs match names starting with `.`,
+    /// so dotfiles are only copied if written out, e.g. `.well-known/*`.
+    ///
+    /// Example in the TOML config file: `passthrough_skip_dotfiles = true`
+    passthrough_skip_dotfiles: bool,
+    /// How many Typst processes to run at once when compiling or querying.
+    ///
+    /// Defaults to the available parallelism of your computer.
+    ///
+    /// Example in the TOML config file: `jobs = 4`
+    jobs: Option<usize>,
+    /// After a full build, delete files in the output directory that no source file compiles or copies to anymore,
+    /// e.g. because the source was deleted or renamed. Also turned on by `--prune-stale`.
+    ///
+    /// Example in the TOML config file: `prune_stale = true`
+    prune_stale: bool,
+    /// Array of globs matching files in the output directory that `prune_stale` must never delete,
+    /// e.g. files put there by another tool.
+    ///
+    /// Globs are rooted in the output directory. Variables are interpolated as described in `passthrough_copy`.
+    ///
+    /// Example in the TOML config file: `preserve = ["CNAME", ".well-known/**"]`
+    preserve: Vec<String>,
+    /// Build the whole site in a directory next to the output directory, starting from a copy of it,
+    /// and swap it in for the output directory only if every file compiled. Also turned on by `--staged-builds`.
+    ///
+    /// A failed build then leaves the previous site untouched, instead of partly updated.
+    ///
+    /// Example in the TOML config file: `staged_builds = true`
+    staged_builds: bool,
+    /// Array of Typst warnings to never show, matched against their messages.
+    /// A warning is suppressed if its message contains any of these.
+    ///
+    /// Variables are interpolated as described in `passthrough_copy`.
+    ///
+    /// Example in the TOML config file: `suppress_warnings = ["html export is under active development"]`
+    suppress_warnings: Vec<String>,
 }
 
-#[derive(Debug)]
+/// A single `[[passthrough_map]]` section. See [`ConfigFile::passthrough_map`].
+#[derive(Default)]
+struct PassthroughMapFile {
+    from: String,
+    to: String,
+}
+
+/// Every key a [`PassthroughMapFile`] can be loaded from. Keep in sync with the struct.
+const PASSTHROUGH_MAP_KEYS: &[&str] = &["from", "to"];
+
+/// A single `[[override]]` section. See [`ConfigFile::overrides`].
+#[derive(Default)]
+struct OverrideFile {
+    glob: String,
+    literal_paths: Option<bool>,
+    post_processing_typ: Option<Vec<String>>,
+    compilation_extra_args: Option<Vec<String>>,
+    inputs: BTreeMap<String, String>,
+}
+
+/// Every key an [`OverrideFile`] can be loaded from. Keep in sync with the struct.
+const OVERRIDE_KEYS: &[&str] = &[
+    "glob",
+    "literal_paths",
+    "post_processing_typ",
+    "compilation_extra_args",
+];
+
+/// Every key a [`ConfigFile`] can be loaded from. Keep in sync with the struct.
+const KNOWN_KEYS: &[&str] = &[
+    "passthrough_copy",
+    "init",
+    "post_processing_typ",
+    "literal_paths",
+    "file_listing",
+    "file_listing_extra_args",
+    "compilation_extra_args",
+    "disable_incremental",
+    "content_dir",
+    "output_dir",
+    "template_dir",
+    "allow_unknown_keys",
+    "override",
+    "exclude",
+    "use_ignore_files",
+    "partial_prefix",
+    "passthrough_map",
+    "passthrough_ignore_case",
+    "passthrough_skip_dotfiles",
+    "jobs",
+    "prune_stale",
+    "preserve",
+    "staged_builds",
+    "suppress_warnings",
+];
+
+/// Every table a [`ConfigFile`] can be loaded from. Their keys are user-defined.
+const KNOWN_TABLES: &[&str] = &["inputs"];
+
+#[derive(Debug, Clone, Copy)]
 pub enum FileListing {
     Disabled,
     Enabled,
@@ -154,6 +548,15 @@ impl FileListing {
     pub const ENABLED_STR: &str = "enabled";
     pub const INCLUDE_DATA_STR: &str = "include-data";
     pub const DEFAULT_STR: &str = Self::DISABLED_STR;
+
+    /// The config file string for this setting.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Disabled => Self::DISABLED_STR,
+            Self::Enabled => Self::ENABLED_STR,
+            Self::IncludeData => Self::INCLUDE_DATA_STR,
+        }
+    }
 }
 
 impl Default for FileListing {
@@ -181,9 +584,17 @@ impl FromStr for FileListing {
     }
 }
 
-pub struct PassthroughCopyGlobs(Vec<Pattern>);
+/// Globs from `passthrough_copy`, and rules from `[[passthrough_map]]` sections.
+#[derive(Clone)]
+pub struct PassthroughCopyGlobs {
+    /// Each glob is paired with whether it is negated, i.e. started with `!`. The last matching glob decides.
+    globs: Vec<(Pattern, bool)>,
+    maps: Vec<PassthroughMap>,
+    match_options: MatchOptions,
+}
 
 impl PassthroughCopyGlobs {
+    /// How globs match by default. `passthrough_ignore_case` and `passthrough_skip_dotfiles` change this for passthrough globs.
     const MATCH_CFG: MatchOptions = MatchOptions {
         case_sensitive: true,
         require_literal_separator: true,
@@ -191,49 +602,267 @@ impl PassthroughCopyGlobs {
     };
 
     pub fn matches_path_with(&self, path: &Path) -> bool {
-        self.0
+        self.globs
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.matches_path_with(&path, self.match_options))
+            .is_some_and(|(_, negated)| !negated)
+    }
+
+    /// Where the first `[[passthrough_map]]` rule matching `path` copies it to, if any.
+    pub fn mapped_destination(&self, path: &Path) -> Option<PathBuf> {
+        self.maps
             .iter()
-            .any(|glob| glob.matches_path_with(&path, Self::MATCH_CFG))
+            .find_map(|map| map.destination(path, self.match_options))
+    }
+
+    pub fn maps(&self) -> &[PassthroughMap] {
+        &self.maps
+    }
+}
+
+/// A `[[passthrough_map]]` rule, resolved to full paths.
+#[derive(Clone)]
+pub struct PassthroughMap {
+    /// Matches the full paths of files to copy.
+    pub from: Pattern,
+    /// The non-wildcard start of `from`. Matched files keep their paths relative to this.
+    pub base: PathBuf,
+    /// The full path to copy matched files into.
+    pub to: PathBuf,
+    /// Whether `to` is the destination file itself, instead of a directory to copy into.
+    pub to_file: bool,
+}
+
+impl PassthroughMap {
+    /// Where this rule copies `path` to, if it matches.
+    pub fn destination(&self, path: &Path, match_options: MatchOptions) -> Option<PathBuf> {
+        if !self.from.matches_path_with(path, match_options) {
+            return None;
+        }
+
+        if self.to_file {
+            Some(self.to.clone())
+        } else {
+            Some(self.to.join(path.strip_prefix(&self.base).ok()?))
+        }
+    }
+}
+
+/// Ignore the gnarly debug impl for `Pattern`.
+impl Debug for PassthroughMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PassthroughMap")
+            .field("from", &self.from.as_str())
+            .field("to", &self.to)
+            .finish()
     }
 }
 
 /// Ignore the gnarly debug impl for `Pattern`.
 impl Debug for PassthroughCopyGlobs {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("omitted for brevity. see below")
+        f.debug_struct("PassthroughCopyGlobs")
+            .field("globs", &"omitted for brevity. see below")
+            .field("maps", &self.maps)
+            .finish()
+    }
+}
+
+/// Globs from `exclude` and, optionally, ignore files.
+///
+/// Each glob is paired with whether it re-includes (from a `!` in an ignore file) instead of excludes.
+/// The last matching glob decides.
+#[derive(Clone)]
+pub struct ExcludeGlobs(Vec<(Pattern, bool)>);
+
+impl ExcludeGlobs {
+    pub const IGNORE_FNAMES: [&str; 2] = [".gitignore", ".ctsignore"];
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.0
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.matches_path_with(path, PassthroughCopyGlobs::MATCH_CFG))
+            .is_some_and(|(_, reinclude)| !reinclude)
+    }
+
+    /// Convert the lines of a `.gitignore`-style file in the directory `base` to globs.
+    ///
+    /// A pattern without a slash in the middle may match at any depth below `base`,
+    /// and a pattern matching a directory matches everything inside it.
+    fn from_ignore_file(base: &Path, contents: &str) -> Result<Vec<(Pattern, bool)>> {
+        let base = Pattern::escape(base.to_str().context(anyhow!("{base:?} not utf8"))?);
+        let mut globs = Vec::new();
+
+        for line in contents.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (line, reinclude) = match line.strip_prefix('!') {
+                Some(line) => (line, true),
+                None => (line, false),
+            };
+            let line = line.strip_suffix('/').unwrap_or(line);
+            let anchored = line.trim_start_matches('/');
+            let glob = if line.contains('/') {
+                format!("{base}/{anchored}")
+            } else {
+                format!("{base}/**/{anchored}")
+            };
+
+            globs.push((glob.parse::<Pattern>()?, reinclude));
+            globs.push((format!("{glob}/**").parse::<Pattern>()?, reinclude));
+        }
+
+        Ok(globs)
+    }
+}
+
+/// Ignore the gnarly debug impl for `Pattern`.
+impl Debug for ExcludeGlobs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|(glob, reinclude)| {
+                format!("{}{}", if *reinclude { "!" } else { "" }, glob.as_str())
+            }))
+            .finish()
+    }
+}
+
+/// Settings from a `[[override]]` section in the config file, for source files matching `glob`.
+#[derive(Clone)]
+pub struct Override {
+    pub glob: Pattern,
+    pub literal_paths: Option<bool>,
+    pub post_processing_typ: Option<Vec<String>>,
+    pub compilation_extra_args: Option<Vec<String>>,
+    pub inputs: BTreeMap<String, String>,
+}
+
+impl Override {
+    /// How specific the glob is, i.e. how many of its characters aren't wildcards.
+    fn specificity(&self) -> usize {
+        self.glob
+            .as_str()
+            .chars()
+            .filter(|c| !matches!(c, '*' | '?' | '[' | ']'))
+            .count()
+    }
+}
+
+/// Ignore the gnarly debug impl for `Pattern`.
+impl Debug for Override {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Override")
+            .field("glob", &self.glob.as_str())
+            .field("literal_paths", &self.literal_paths)
+            .field("post_processing_typ", &self.post_processing_typ)
+            .field("compilation_extra_args", &self.compilation_extra_args)
+            .field("inputs", &self.inputs)
+            .finish()
+    }
+}
+
+/// The settings for a single source file, after applying any matching [`Override`]s.
+#[derive(Debug)]
+pub struct FileSettings<'a> {
+    pub literal_paths: bool,
+    pub post_processing_typ: &'a [String],
+    pub compilation_extra_args: &'a [String],
+    pub inputs: BTreeMap<String, String>,
+}
+
+impl FileSettings<'_> {
+    /// `--input KEY=VALUE` arguments for each of the inputs, to pass to `typst compile` or `typst query`.
+    pub fn typst_input_args(&self) -> Vec<String> {
+        self.inputs
+            .iter()
+            .flat_map(|(key, value)| ["--input".to_string(), format!("{key}={value}")])
+            .collect()
     }
 }
 
 /// Full config after taking in command line arguments, a configuration file, and other post-computations.
 ///
 /// See [`Args`] and [`ConfigFile`] for documentation of fields.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Config {
+    pub subcommand: Subcommand,
+    /// The build profile whose overrides were applied, if any.
+    pub profile: Option<String>,
     pub watch: bool,
     pub serve: bool,
     pub disable_incremental: bool,
     pub ignore_initial: bool,
     pub verbose: bool,
     pub trace: bool,
+    /// Whether to skip compiling pages that haven't changed since they were cached, and cache the ones that did.
+    pub use_cache: bool,
     pub color: LogWithColor,
     pub passthrough_copy: Vec<String>,
     pub passthrough_copy_globs: PassthroughCopyGlobs,
     // Pattern has gnarly debug impl; emit a String version instead.
     pub passthrough_copy_globs_string_form: Vec<String>,
+    pub passthrough_ignore_case: bool,
+    pub passthrough_skip_dotfiles: bool,
     pub init: Vec<String>,
     pub post_processing_typ: Vec<String>,
     pub literal_paths: bool,
     pub file_listing: FileListing,
     pub file_listing_extra_args: Vec<String>,
     pub compilation_extra_args: Vec<String>,
+    pub inputs: BTreeMap<String, String>,
+    /// Sorted from least to most specific.
+    pub overrides: Vec<Override>,
+    pub exclude: Vec<String>,
+    pub use_ignore_files: bool,
+    pub exclude_globs: ExcludeGlobs,
+    pub partial_prefix: String,
+    /// How many jobs to run at once. At least 1.
+    pub jobs: usize,
+    pub prune_stale: bool,
+    pub preserve: Vec<String>,
+    /// `preserve`, rooted in the output root.
+    pub preserve_globs: Vec<Pattern>,
+    pub staged_builds: bool,
+    /// Typst warnings whose messages contain any of these are never shown.
+    pub suppress_warnings: Vec<String>,
+    /// Whether to summarize every file that failed to compile, instead of only reporting the first.
+    pub keep_going: bool,
+    /// Where to write a JSON report after each full build.
+    pub report: Option<PathBuf>,
+    /// Whether to write a chart of the processes each full build ran to [`Config::timings_root`].
+    pub timings: bool,
     pub project_root: PathBuf,
     pub content_relpath: PathBuf,
     pub output_relpath: PathBuf,
     pub template_relpath: PathBuf,
+    /// Where each value that isn't a default came from, keyed like the config file, e.g. `inputs.KEY`.
+    pub sources: BTreeMap<String, ConfigSource>,
 }
 pub const CONFIG_FNAME: &str = "compile-typst-site.toml";
+pub const DEFAULT_CONTENT_DIR: &str = "src";
+pub const DEFAULT_OUTPUT_DIR: &str = "_site";
+pub const DEFAULT_TEMPLATE_DIR: &str = "templates";
+pub const DEFAULT_PARTIAL_PREFIX: &str = "_";
+/// Where the build cache lives, relative to the project root.
+pub const CACHE_DIR: &str = ".cts-cache";
+/// Profile used when watching or serving and no `--profile` is given.
+pub const DEFAULT_DEV_PROFILE: &str = "dev";
+/// `sys.inputs` key telling templates whether we are building once or watching/serving.
+pub const MODE_INPUT: &str = "cts-mode";
 
 impl Config {
+    /// Where the value of the config file key `key` came from.
+    pub fn source(&self, key: &str) -> ConfigSource {
+        self.sources
+            .get(key)
+            .cloned()
+            .unwrap_or(ConfigSource::Default)
+    }
+
     pub fn content_root(&self) -> PathBuf {
         self.project_root.join(&self.content_relpath)
     }
@@ -242,15 +871,203 @@ impl Config {
         self.project_root.join(&self.output_relpath)
     }
 
+    pub fn cache_root(&self) -> PathBuf {
+        self.project_root.join(CACHE_DIR)
+    }
+
+    /// Where full builds are staged with `staged_builds`. Next to the output root, so it can be renamed to it.
+    pub fn staging_root(&self) -> PathBuf {
+        self.output_sibling("staging")
+    }
+
+    /// Where the previous output is moved while the staged build replaces it.
+    pub fn replaced_output_root(&self) -> PathBuf {
+        self.output_sibling("old")
+    }
+
+    /// Where the file listing is written, unless `file_listing` is disabled.
+    pub fn file_listing_path(&self) -> PathBuf {
+        self.project_root.join("files.json")
+    }
+
+    /// Where `--timings` charts are written. Next to the output root, so they aren't deployed with the site.
+    pub fn timings_root(&self) -> PathBuf {
+        self.output_root().with_file_name("cts-timings")
+    }
+
+    fn output_sibling(&self, suffix: &str) -> PathBuf {
+        let output_root = self.output_root();
+        let name = output_root
+            .file_name()
+            .map_or_else(|| "output".into(), |name| name.to_string_lossy());
+        output_root.with_file_name(format!(".{name}.{suffix}"))
+    }
+
+    /// Directories we write to, which must not be read as sources or trigger rebuilds if they're under the content root.
+    pub fn generated_roots(&self) -> [PathBuf; 5] {
+        [
+            self.output_root(),
+            self.cache_root(),
+            self.staging_root(),
+            self.replaced_output_root(),
+            self.timings_root(),
+        ]
+    }
+
+    /// Files we write next to the sources, which must not trigger rebuilds either. Absolute, so they can be compared with
+    /// the paths the watcher reports however the project root or `--report` were given.
+    pub fn generated_files(&self) -> Vec<PathBuf> {
+        [Some(self.file_listing_path()), self.report.clone()]
+            .into_iter()
+            .flatten()
+            .map(|path| std::path::absolute(&path).unwrap_or(path))
+            .collect()
+    }
+
+    /// Whether the output root contains the project, content, or template root, so deleting what's in it that we
+    /// didn't produce could delete sources.
+    pub fn output_root_holds_sources(&self) -> bool {
+        Self::source_root_under(
+            &self.output_root(),
+            &self.project_root,
+            &self.content_relpath,
+            &self.template_relpath,
+        )
+        .is_some()
+    }
+
+    /// The first of the project, content, and template roots that's under `output_root`.
+    fn source_root_under(
+        output_root: &Path,
+        project_root: &Path,
+        content_relpath: &Path,
+        template_relpath: &Path,
+    ) -> Option<PathBuf> {
+        // canonicalize so e.g. `output_dir = ".."` is caught too.
+        let resolve = |path: PathBuf| fs::canonicalize(&path).unwrap_or(path);
+        let output_root = resolve(output_root.to_path_buf());
+        [
+            project_root.to_path_buf(),
+            project_root.join(content_relpath),
+            project_root.join(template_relpath),
+        ]
+        .into_iter()
+        .find(|root| resolve(root.clone()).starts_with(&output_root))
+    }
+
+    /// This config, but for the project at `project_root`, with the directories and file listing its config file sets.
+    ///
+    /// Used to tell which files of another project are generated, e.g. to scaffold from it.
+    pub fn for_project(&self, project_root: &Path) -> Result<Config> {
+        let (configfile, _) = Self::get_configfile(project_root, None, false)?;
+        let mut config = self.clone();
+        config.project_root = project_root.to_path_buf();
+        config.content_relpath = configfile
+            .content_dir
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONTENT_DIR));
+        config.output_relpath = configfile
+            .output_dir
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_OUTPUT_DIR));
+        config.template_relpath = configfile
+            .template_dir
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_TEMPLATE_DIR));
+        config.file_listing = configfile.file_listing;
+        Ok(config)
+    }
+
+    /// This config, but writing to [`Config::staging_root`] instead of the output root.
+    pub fn staged(&self) -> Result<Config> {
+        let mut staged = self.clone();
+        staged.staged_builds = false;
+        staged.output_relpath = self.output_relpath.with_file_name(
+            self.staging_root()
+                .file_name()
+                .expect("staging root has a name"),
+        );
+        let output_root = self.output_root();
+        let staging_root = staged.output_root();
+        for map in staged.passthrough_copy_globs.maps.iter_mut() {
+            if let Ok(rel_to) = map.to.strip_prefix(&output_root) {
+                map.to = staging_root.join(rel_to);
+            }
+        }
+        (staged.preserve_globs, _) = Self::compile_globs(
+            &staged.preserve,
+            &staged.project_root,
+            &staged.output_relpath,
+        )?;
+        Ok(staged)
+    }
+
     pub fn template_root(&self) -> PathBuf {
         self.project_root.join(&self.template_relpath)
     }
 
-    pub fn new() -> Result<Self> {
-        let content_relpath = PathBuf::from("src");
-        let output_relpath = PathBuf::from("_site");
-        let template_relpath = PathBuf::from("templates");
+    /// Whether the Typst file at `path` is a partial, i.e. it or a parent directory under the content root starts with [`Config::partial_prefix`].
+    pub fn is_partial(&self, path: &Path) -> bool {
+        if self.partial_prefix.is_empty() || path.extension() != Some(OsStr::new("typ")) {
+            return false;
+        }
+
+        path.strip_prefix(self.content_root())
+            .is_ok_and(|rel_path| {
+                rel_path.components().any(|c| {
+                    c.as_os_str()
+                        .to_string_lossy()
+                        .starts_with(&self.partial_prefix)
+                })
+            })
+    }
+
+    /// Whether the Typst warning `message` is one of [`Config::suppress_warnings`].
+    pub fn is_warning_suppressed(&self, message: &str) -> bool {
+        self.suppress_warnings
+            .iter()
+            .any(|suppressed| message.contains(suppressed.as_str()))
+    }
+
+    /// Whether `prune_stale` must keep the file at `path` in the output root, because it matches [`Config::preserve_globs`].
+    pub fn is_preserved(&self, path: &Path) -> bool {
+        self.preserve_globs
+            .iter()
+            .any(|glob| glob.matches_path_with(path, PassthroughCopyGlobs::MATCH_CFG))
+    }
+
+    /// Compute the settings for the source file at `path`, applying matching [`Override`]s.
+    pub fn file_settings(&self, path: &Path) -> FileSettings<'_> {
+        let mut settings = FileSettings {
+            literal_paths: self.literal_paths,
+            post_processing_typ: &self.post_processing_typ,
+            compilation_extra_args: &self.compilation_extra_args,
+            inputs: self.inputs.clone(),
+        };
+
+        // sorted least to most specific, so the most specific is applied last and wins.
+        for o in &self.overrides {
+            if !o
+                .glob
+                .matches_path_with(path, PassthroughCopyGlobs::MATCH_CFG)
+            {
+                continue;
+            }
+            if let Some(literal_paths) = o.literal_paths {
+                settings.literal_paths = literal_paths;
+            }
+            if let Some(post_processing_typ) = &o.post_processing_typ {
+                settings.post_processing_typ = post_processing_typ;
+            }
+            if let Some(compilation_extra_args) = &o.compilation_extra_args {
+                settings.compilation_extra_args = compilation_extra_args;
+            }
+            settings
+                .inputs
+                .extend(o.inputs.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+
+        settings
+    }
 
+    pub fn new() -> Result<Self> {
         let Args {
             path,
             watch,
@@ -258,7 +1075,21 @@ impl Config {
             ignore_initial,
             verbose,
             trace,
+            no_cache,
+            prune_stale: cli_prune_stale,
+            staged_builds: cli_staged_builds,
+            keep_going,
+            timings,
+            report,
             color,
+            input,
+            src,
+            out,
+            profile,
+            jobs: cli_jobs,
+            from,
+            format,
+            command,
         } = onlyargs::parse()?;
 
         let color = match color {
@@ -266,44 +1097,282 @@ impl Config {
             None => LogWithColor::default(),
         };
 
-        // map with Ok, or else search for the root, then ?
-        let project_root = path.map_or_else(Self::get_project_root, Ok)?;
+        let subcommand = Subcommand::parse(command, path.as_deref(), from, format)?;
+        let mut sources = BTreeMap::new();
+        if path.is_some() {
+            sources.insert("project_root".to_string(), ConfigSource::CommandLine);
+        }
+
+        // a new project has no config file yet, so don't look for one.
+        let project_root = match &subcommand {
+            Subcommand::Build | Subcommand::Config { .. } | Subcommand::Clean => {
+                // map with Ok, or else search for the root, then ?
+                path.map_or_else(Self::get_project_root, Ok)?
+            }
+            Subcommand::New { dir, .. } => dir.clone(),
+            // these don't touch a project.
+            Subcommand::Completions { .. } | Subcommand::Man => std::env::current_dir()?,
+        };
+
+        // a profile asked for by name must exist, but the implicit dev profile is optional.
+        let (profile, profile_required) = match profile {
+            Some(profile) => {
+                sources.insert("profile".to_string(), ConfigSource::CommandLine);
+                (Some(profile), true)
+            }
+            None if watch || serve => (Some(DEFAULT_DEV_PROFILE.to_string()), false),
+            None => (None, false),
+        };
 
         let ConfigFile {
-            passthrough_copy,
-            init,
-            post_processing_typ,
+            mut passthrough_copy,
+            mut init,
+            mut post_processing_typ,
             literal_paths,
             file_listing,
-            file_listing_extra_args,
-            compilation_extra_args,
+            mut file_listing_extra_args,
+            mut compilation_extra_args,
             disable_incremental,
-        } = Self::get_configfile(&project_root)?;
+            content_dir,
+            output_dir,
+            template_dir,
+            allow_unknown_keys: _,
+            mut inputs,
+            overrides,
+            mut exclude,
+            use_ignore_files,
+            partial_prefix,
+            mut passthrough_map,
+            passthrough_ignore_case,
+            passthrough_skip_dotfiles,
+            jobs,
+            prune_stale,
+            mut preserve,
+            staged_builds,
+            mut suppress_warnings,
+        } = match &subcommand {
+            Subcommand::Build | Subcommand::Config { .. } => {
+                let (configfile, file_sources) =
+                    Self::get_configfile(&project_root, profile.as_deref(), profile_required)?;
+                sources.extend(file_sources);
+                configfile
+            }
+            _ => ConfigFile::default(),
+        };
 
-        let (passthrough_copy_globs, passthrough_copy_globs_string_form) =
-            Self::compile_globs(&passthrough_copy, &project_root, &content_relpath)?;
+        // command-line arguments take precedence over the config file
+        if src.is_some() {
+            sources.insert("content_dir".to_string(), ConfigSource::CommandLine);
+        }
+        if out.is_some() {
+            sources.insert("output_dir".to_string(), ConfigSource::CommandLine);
+        }
+        let content_relpath = src
+            .or(content_dir)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONTENT_DIR));
+        let output_relpath = out
+            .or(output_dir)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_OUTPUT_DIR));
+        let template_relpath = template_dir.unwrap_or_else(|| PathBuf::from(DEFAULT_TEMPLATE_DIR));
+        let partial_prefix = partial_prefix.unwrap_or_else(|| DEFAULT_PARTIAL_PREFIX.to_string());
+        if cli_jobs.is_some() {
+            sources.insert("jobs".to_string(), ConfigSource::CommandLine);
+        }
+        let jobs = match cli_jobs.or(jobs) {
+            Some(0) => return Err(anyhow!("jobs must be at least 1")),
+            Some(jobs) => jobs,
+            None => std::thread::available_parallelism().map_or(1, usize::from),
+        };
+        if cli_prune_stale {
+            sources.insert("prune_stale".to_string(), ConfigSource::CommandLine);
+        }
+        let prune_stale = cli_prune_stale || prune_stale;
+        if cli_staged_builds {
+            sources.insert("staged_builds".to_string(), ConfigSource::CommandLine);
+        }
+        let staged_builds = cli_staged_builds || staged_builds;
+        // both delete or replace the output directory, so it mustn't hold anything else we need.
+        for (enabled, option) in [
+            (prune_stale, "prune_stale"),
+            (staged_builds, "staged_builds"),
+        ] {
+            if !enabled {
+                continue;
+            }
+            if let Some(root) = Self::source_root_under(
+                &project_root.join(&output_relpath),
+                &project_root,
+                &content_relpath,
+                &template_relpath,
+            ) {
+                return Err(anyhow!(
+                    "{option} can't be used when the output directory {} contains {}, since it would delete it.",
+                    project_root.join(&output_relpath).to_string_lossy(),
+                    root.to_string_lossy()
+                ));
+            }
+        }
+
+        let variables = [
+            ("PROJECT_ROOT", project_root.to_string_lossy().to_string()),
+            (
+                "CONTENT_ROOT",
+                project_root
+                    .join(&content_relpath)
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+            (
+                "OUTPUT_ROOT",
+                project_root
+                    .join(&output_relpath)
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+            (
+                "TEMPLATE_ROOT",
+                project_root
+                    .join(&template_relpath)
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+        ];
+        for (field, strs) in [
+            ("passthrough_copy", &mut passthrough_copy),
+            ("exclude", &mut exclude),
+            ("preserve", &mut preserve),
+            ("suppress_warnings", &mut suppress_warnings),
+            ("init", &mut init),
+            ("post_processing_typ", &mut post_processing_typ),
+            ("file_listing_extra_args", &mut file_listing_extra_args),
+            ("compilation_extra_args", &mut compilation_extra_args),
+        ] {
+            for s in strs.iter_mut() {
+                *s = Self::interpolate(s, &variables)
+                    .context(anyhow!("Couldn't interpolate variables in {field}: {s:?}"))?;
+            }
+        }
+        for (key, value) in inputs.iter_mut() {
+            *value = Self::interpolate(value, &variables).context(anyhow!(
+                "Couldn't interpolate variables in inputs.{key}: {value:?}"
+            ))?;
+        }
+        for map in passthrough_map.iter_mut() {
+            for s in [&mut map.from, &mut map.to] {
+                *s = Self::interpolate(s, &variables).context(anyhow!(
+                    "Couldn't interpolate variables in passthrough_map: {s:?}"
+                ))?;
+            }
+        }
+
+        // these beat both the config file's inputs and those in overrides.
+        let mut forced_inputs = BTreeMap::new();
+        for input in input {
+            let (key, value) = input
+                .split_once('=')
+                .context(anyhow!("--input must be given as KEY=VALUE, not {input:?}"))?;
+            forced_inputs.insert(key.to_string(), value.to_string());
+        }
+        for key in forced_inputs.keys() {
+            sources.insert(format!("inputs.{key}"), ConfigSource::CommandLine);
+        }
+
+        let mut overrides = overrides
+            .into_iter()
+            .map(|o| Self::compile_override(o, &variables, &project_root, &content_relpath))
+            .collect::<Result<Vec<_>>>()?;
+        overrides.sort_by_key(Override::specificity);
+        for o in overrides.iter_mut() {
+            o.inputs.retain(|key, _| !forced_inputs.contains_key(key));
+        }
+        inputs.extend(forced_inputs);
+        // watching or serving means dev mode, unless the config file or command line says otherwise.
+        if (watch || serve)
+            && !inputs.contains_key(MODE_INPUT)
+            && !overrides.iter().any(|o| o.inputs.contains_key(MODE_INPUT))
+        {
+            inputs.insert(MODE_INPUT.to_string(), "dev".to_string());
+            sources.insert(format!("inputs.{MODE_INPUT}"), ConfigSource::CommandLine);
+        }
+
+        let (negated, unprefixed): (Vec<bool>, Vec<String>) = passthrough_copy
+            .iter()
+            .map(|glob| match glob.strip_prefix('!') {
+                Some(glob) => (true, glob.to_string()),
+                None => (false, glob.clone()),
+            })
+            .unzip();
+        let (globs, mut passthrough_copy_globs_string_form) =
+            Self::compile_globs(&unprefixed, &project_root, &content_relpath)?;
+        for (string_form, _) in passthrough_copy_globs_string_form
+            .iter_mut()
+            .zip(&negated)
+            .filter(|(_, negated)| **negated)
+        {
+            string_form.insert(0, '!');
+        }
+        let passthrough_copy_globs = PassthroughCopyGlobs {
+            globs: globs.into_iter().zip(negated).collect(),
+            maps: passthrough_map
+                .into_iter()
+                .map(|map| Self::compile_passthrough_map(map, &project_root, &output_relpath))
+                .collect::<Result<_>>()?,
+            match_options: MatchOptions {
+                case_sensitive: !passthrough_ignore_case,
+                require_literal_leading_dot: passthrough_skip_dotfiles,
+                ..PassthroughCopyGlobs::MATCH_CFG
+            },
+        };
+        let (preserve_globs, _) = Self::compile_globs(&preserve, &project_root, &output_relpath)?;
+        let exclude_globs = Self::compile_exclude_globs(
+            &exclude,
+            use_ignore_files,
+            &project_root,
+            &content_relpath,
+        )?;
 
         Ok(Self {
+            subcommand,
+            profile,
             watch,
             serve,
             ignore_initial,
             verbose,
             trace,
+            use_cache: !no_cache,
             color,
             passthrough_copy,
             passthrough_copy_globs,
             passthrough_copy_globs_string_form,
+            passthrough_ignore_case,
+            passthrough_skip_dotfiles,
             init,
             post_processing_typ,
             literal_paths,
             file_listing,
             file_listing_extra_args,
             compilation_extra_args,
+            inputs,
+            overrides,
+            exclude,
+            use_ignore_files,
+            exclude_globs,
+            partial_prefix,
+            jobs,
+            prune_stale,
+            preserve,
+            preserve_globs,
+            staged_builds,
+            suppress_warnings,
+            keep_going: keep_going || watch || serve,
+            report,
+            timings,
             disable_incremental,
             project_root,
             content_relpath,
             output_relpath,
             template_relpath,
+            sources,
         })
     }
 
@@ -325,11 +1394,92 @@ impl Config {
         }
     }
 
+    /// Compile `exclude` globs, followed by those from ignore files if `use_ignore_files`.
+    fn compile_exclude_globs(
+        exclude: &[String],
+        use_ignore_files: bool,
+        project_root: &Path,
+        content_root: &Path,
+    ) -> Result<ExcludeGlobs> {
+        let (globs, _) = Self::compile_globs(exclude, project_root, content_root)?;
+        let mut globs: Vec<(Pattern, bool)> = globs.into_iter().map(|glob| (glob, false)).collect();
+
+        if use_ignore_files {
+            let dirs = std::iter::once(project_root.to_path_buf()).chain(
+                WalkDir::new(project_root.join(content_root))
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter(|entry| entry.file_type().is_dir())
+                    .map(|entry| entry.into_path()),
+            );
+            // parents before children, so more deeply nested ignore files take precedence.
+            for dir in dirs {
+                for fname in ExcludeGlobs::IGNORE_FNAMES {
+                    let Ok(contents) = fs::read_to_string(dir.join(fname)) else {
+                        continue;
+                    };
+                    globs.extend(ExcludeGlobs::from_ignore_file(&dir, &contents).context(
+                        anyhow!("Couldn't read the ignore file {:?}", dir.join(fname)),
+                    )?);
+                }
+            }
+        }
+
+        Ok(ExcludeGlobs(globs))
+    }
+
+    /// Interpolate variables in and compile the glob of an [`OverrideFile`].
+    fn compile_override(
+        o: OverrideFile,
+        variables: &[(&str, String)],
+        project_root: &Path,
+        content_root: &Path,
+    ) -> Result<Override> {
+        let OverrideFile {
+            glob,
+            literal_paths,
+            mut post_processing_typ,
+            mut compilation_extra_args,
+            mut inputs,
+        } = o;
+
+        let context = || anyhow!("Couldn't load the [[override]] with glob {glob:?}");
+
+        for strs in [&mut post_processing_typ, &mut compilation_extra_args]
+            .into_iter()
+            .flatten()
+        {
+            for s in strs.iter_mut() {
+                *s = Self::interpolate(s, variables).with_context(context)?;
+            }
+        }
+        for value in inputs.values_mut() {
+            *value = Self::interpolate(value, variables).with_context(context)?;
+        }
+
+        let glob = Self::interpolate(&glob, variables).with_context(context)?;
+        let glob = project_root
+            .join(content_root)
+            .join(&glob)
+            .to_str()
+            .context(anyhow!("{glob} not utf8"))?
+            .parse::<Pattern>()
+            .with_context(context)?;
+
+        Ok(Override {
+            glob,
+            literal_paths,
+            post_processing_typ,
+            compilation_extra_args,
+            inputs,
+        })
+    }
+
     fn compile_globs(
         string_globs: &[String],
         project_root: &Path,
         content_root: &Path,
-    ) -> Result<(PassthroughCopyGlobs, Vec<String>)> {
+    ) -> Result<(Vec<Pattern>, Vec<String>)> {
         let mut compiled_globs = Vec::new();
         let mut compiled_globs_string_form = Vec::new();
 
@@ -346,11 +1496,131 @@ impl Config {
             compiled_globs_string_form.push(string_glob);
         }
 
-        let compiled_globs = PassthroughCopyGlobs(compiled_globs);
-
         Ok((compiled_globs, compiled_globs_string_form))
     }
 
+    /// Resolve a `[[passthrough_map]]` rule, whose `from` is rooted in the project root and `to` in the output root.
+    fn compile_passthrough_map(
+        map: PassthroughMapFile,
+        project_root: &Path,
+        output_root: &Path,
+    ) -> Result<PassthroughMap> {
+        /// Resolve `.` and `..` without touching the filesystem, so paths outside the project root match walked paths.
+        fn normalize(path: &Path) -> PathBuf {
+            let mut normalized = PathBuf::new();
+            for component in path.components() {
+                match component {
+                    Component::CurDir => (),
+                    Component::ParentDir if normalized.file_name().is_some() => {
+                        normalized.pop();
+                    }
+                    component => normalized.push(component),
+                }
+            }
+            normalized
+        }
+
+        let from = normalize(&project_root.join(&map.from));
+        let base: PathBuf = from
+            .components()
+            .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+            .collect();
+        let has_wildcards = base != from;
+        let to_file = !has_wildcards && !map.to.ends_with('/');
+        let output_root = normalize(&project_root.join(output_root));
+        let to = normalize(&output_root.join(&map.to));
+        if to.strip_prefix(&output_root).is_err() {
+            return Err(anyhow!(
+                "passthrough_map can't copy {:?} outside the output directory, to {:?}",
+                map.from,
+                map.to
+            ));
+        }
+
+        Ok(PassthroughMap {
+            from: from
+                .to_str()
+                .context(anyhow!("{} not utf8", map.from))?
+                .parse::<Pattern>()?,
+            // a single file is copied into `to` by name.
+            base: if has_wildcards {
+                base
+            } else {
+                from.parent().context("Found no parent.")?.to_path_buf()
+            },
+            to,
+            to_file,
+        })
+    }
+
+    /// Replace `$NAME`, `${NAME}`, `${env:NAME}`, `${env:NAME:-default}`, and `$$` in a config file string.
+    ///
+    /// Errors on unknown variables, unset environment variables without defaults, and stray `$`s.
+    fn interpolate(s: &str, variables: &[(&str, String)]) -> Result<String> {
+        let lookup = |name: &str| -> Result<String> {
+            if let Some(env) = name.strip_prefix("env:") {
+                let (env, default) = match env.split_once(":-") {
+                    Some((env, default)) => (env, Some(default)),
+                    None => (env, None),
+                };
+                return match (std::env::var(env), default) {
+                    (Ok(value), _) => Ok(value),
+                    (Err(_), Some(default)) => Ok(default.to_string()),
+                    (Err(e), None) => Err(anyhow!(
+                        "environment variable {env} couldn't be read ({e}). \
+                        Provide a default with ${{env:{env}:-default}}"
+                    )),
+                };
+            }
+
+            variables
+                .iter()
+                .find(|(known, _)| *known == name)
+                .map(|(_, value)| value.clone())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "unknown variable ${name}. Known variables are {}, ${{env:NAME}}, \
+                        and $$ for a literal $",
+                        variables
+                            .iter()
+                            .map(|(known, _)| format!("${known}"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })
+        };
+
+        let mut result = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(pos) = rest.find('$') {
+            result.push_str(&rest[..pos]);
+            rest = &rest[pos + 1..];
+
+            if let Some(after) = rest.strip_prefix('$') {
+                result.push('$');
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('{') {
+                let end = after.find('}').context(anyhow!("unclosed ${{ in {s:?}"))?;
+                result.push_str(&lookup(&after[..end])?);
+                rest = &after[end + 1..];
+            } else {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                if end == 0 {
+                    return Err(anyhow!(
+                        "found a $ not followed by a variable name. Write $$ for a literal $"
+                    ));
+                }
+                result.push_str(&lookup(&rest[..end])?);
+                rest = &rest[end..];
+            }
+        }
+        result.push_str(rest);
+
+        Ok(result)
+    }
+
     /// Destructively convert a toml value to an array of Strings.
     ///
     /// Do not rely on the array passed in after using this function.
@@ -375,15 +1645,331 @@ impl Config {
         }
     }
 
-    fn get_configfile(project_root: &Path) -> Result<ConfigFile> {
-        const PROJ_ROOT_REPLACEE: &str = "$PROJECT_ROOT";
+    /// Replace top-level keys with those in the `[profile.NAME]` section, then drop all profile sections.
+    ///
+    /// Errors if `required` and the config file has no such profile.
+    fn apply_profile(
+        given: &mut BTreeMap<String, Toml>,
+        profile: Option<&str>,
+        required: bool,
+    ) -> Result<Vec<String>> {
+        const PROFILE_PREFIX: &str = "profile.";
+
+        let profile_keys: Vec<String> = given
+            .keys()
+            .filter(|key| key.starts_with(PROFILE_PREFIX))
+            .cloned()
+            .collect();
+        let mut overrides = Vec::new();
+        for key in profile_keys {
+            let value = given.remove(&key).expect("key was just read from the map");
+            if let Some(profile) = profile
+                && let Some(field) = key[PROFILE_PREFIX.len()..]
+                    .strip_prefix(profile)
+                    .and_then(|rest| rest.strip_prefix('.'))
+            {
+                overrides.push((field.to_string(), value));
+            }
+        }
+
+        if let Some(profile) = profile
+            && overrides.is_empty()
+            && required
+        {
+            return Err(anyhow!(
+                "Profile \"{profile}\" was requested, but {CONFIG_FNAME} has no [profile.{profile}] section."
+            ));
+        }
+
+        let fields = overrides.iter().map(|(field, _)| field.clone()).collect();
+        given.extend(overrides);
+
+        Ok(fields)
+    }
+
+    /// Error on any key in the config file that isn't in [`KNOWN_KEYS`], suggesting the nearest known key.
+    fn validate_keys(given: &BTreeMap<String, Toml>, contents: &str) -> Result<()> {
+        let mut problems = Vec::new();
+
+        let mut check = |key: &str, field: &str, keys: &[&'static str]| {
+            // keys in other tables are flattened to `table.key`
+            let (candidates, name) = match field.split_once('.') {
+                Some((table, _)) => (KNOWN_TABLES, table),
+                None => (keys, field),
+            };
+            if candidates.contains(&name) {
+                return;
+            }
+
+            let mut problem = format!("unknown key `{key}`");
+            let needle = field.rsplit('.').next().unwrap_or(field);
+            if let Some(line) = Self::line_of_key(contents, needle) {
+                problem.push_str(&format!(" on line {line}"));
+            }
+            let suggestion = Self::nearest_known_key(name, candidates);
+            let per_file = keys == OVERRIDE_KEYS && KNOWN_KEYS.contains(&name);
+            if per_file {
+                problem.push_str(&format!(". `{name}` can't be set per file"));
+            } else if let Some(suggestion) = suggestion {
+                problem.push_str(&format!(". Did you mean `{suggestion}`?"));
+            }
+            if per_file || (keys == OVERRIDE_KEYS && suggestion.is_none()) {
+                // `glob` picks the files rather than changing how they're built.
+                let supported: Vec<String> = OVERRIDE_KEYS[1..]
+                    .iter()
+                    .chain(KNOWN_TABLES)
+                    .map(|key| format!("`{key}`"))
+                    .collect();
+                problem.push_str(&format!(
+                    ". Overrides only support {}",
+                    supported.join(", ")
+                ));
+            }
+            problems.push(problem);
+        };
+
+        for (key, value) in given {
+            // keys in profile sections are flattened to `profile.NAME.key`
+            let field = match key.strip_prefix("profile.") {
+                Some(rest) => rest.split_once('.').map_or(rest, |(_, field)| field),
+                None => key.as_str(),
+            };
+            check(key, field, KNOWN_KEYS);
 
+            let table_keys = match field {
+                "override" => OVERRIDE_KEYS,
+                "passthrough_map" => PASSTHROUGH_MAP_KEYS,
+                _ => continue,
+            };
+            if let Toml::Array(tables) = value {
+                for table_key in tables.iter().flat_map(|t| t.keys()) {
+                    check(&format!("{key}.{table_key}"), table_key, table_keys);
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{CONFIG_FNAME} contained unknown keys:\n  {}\n\
+                Set `allow_unknown_keys = true` to ignore them instead.",
+                problems.join("\n  ")
+            ))
+        }
+    }
+
+    /// Find the 1-indexed line a key is first assigned on, if we can.
+    ///
+    /// Our TOML parser doesn't keep track of spans, so this just looks for `key =`.
+    fn line_of_key(contents: &str, key: &str) -> Option<usize> {
+        contents
+            .lines()
+            .position(|line| {
+                let line = line.trim_start();
+                let line = line
+                    .strip_prefix('"')
+                    .and_then(|line| line.strip_prefix(key))
+                    .and_then(|line| line.strip_prefix('"'))
+                    .or_else(|| line.strip_prefix(key));
+                line.is_some_and(|rest| rest.trim_start().starts_with('='))
+            })
+            .map(|i| i + 1)
+    }
+
+    /// The candidate closest to `key` by edit distance, if any is close enough to be a plausible typo.
+    fn nearest_known_key(key: &str, candidates: &[&'static str]) -> Option<&'static str> {
+        fn edit_distance(a: &str, b: &str) -> usize {
+            let b: Vec<char> = b.chars().collect();
+            let mut prev: Vec<usize> = (0..=b.len()).collect();
+            for (i, ca) in a.chars().enumerate() {
+                let mut curr = vec![i + 1];
+                for (j, cb) in b.iter().enumerate() {
+                    let substitution = prev[j] + usize::from(ca != *cb);
+                    curr.push(substitution.min(prev[j + 1] + 1).min(curr[j] + 1));
+                }
+                prev = curr;
+            }
+            prev[b.len()]
+        }
+
+        let max_distance = (key.chars().count() / 3).max(2);
+        candidates
+            .iter()
+            .map(|known| (edit_distance(key, known), *known))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min()
+            .map(|(_, known)| known)
+    }
+
+    /// Parse TOML, working around our parser's handling of `[[array]]` tables.
+    ///
+    /// `nanoserde` prefixes keys in an `[[array]]` element with the name of the preceding `[table]`,
+    /// and puts keys of any later `[table]` into the last array element.
+    /// So, we parse each section on its own and merge the results,
+    /// putting `[array.table]` sections into the last element of the array as TOML intends.
+    fn parse_toml(contents: &str) -> Result<BTreeMap<String, Toml>> {
+        fn is_header(line: &str) -> bool {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            line.strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+                .map(|name| name.trim_start_matches('[').trim_end_matches(']'))
+                .is_some_and(|name| {
+                    !name.is_empty()
+                        && name
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+                })
+        }
+
+        let lines: Vec<&str> = contents.lines().collect();
+        let mut section_starts: Vec<usize> = (0..lines.len())
+            .filter(|&i| i == 0 || is_header(lines[i]))
+            .collect();
+        section_starts.push(lines.len());
+
+        let mut merged = BTreeMap::new();
+        for bounds in section_starts.windows(2) {
+            let (start, end) = (bounds[0], bounds[1]);
+            let section = TomlParser::parse(&lines[start..end].join("\n")).map_err(|mut e| {
+                e.line += start;
+                e
+            })?;
+
+            for (key, value) in section {
+                // a `[array.table]` after `[[array]]` belongs to the last element of the array.
+                let parent_array = key
+                    .match_indices('.')
+                    .map(|(i, _)| &key[..i])
+                    .find(|prefix| matches!(merged.get(*prefix), Some(Toml::Array(_))))
+                    .map(str::to_string);
+                if let Some(parent_array) = parent_array
+                    && let Some(Toml::Array(elements)) = merged.get_mut(&parent_array)
+                    && let Some(last) = elements.last_mut()
+                {
+                    last.insert(key[parent_array.len() + 1..].to_string(), value);
+                    continue;
+                }
+
+                match (merged.get_mut(&key), value) {
+                    (Some(Toml::Array(existing)), Toml::Array(new)) => existing.extend(new),
+                    (_, value) => {
+                        merged.insert(key, value);
+                    }
+                }
+            }
+        }
+
+        Ok(merged)
+    }
+
+    /// Load the inputs table from a parsed config file or `[[override]]`, where they are flattened to `inputs.KEY`.
+    fn load_inputs(given: &mut BTreeMap<String, Toml>) -> Result<BTreeMap<String, String>> {
+        let mut inputs = BTreeMap::new();
+        for (key, value) in given.iter_mut() {
+            let Some(input) = key.strip_prefix("inputs.") else {
+                continue;
+            };
+            let value = match value {
+                Toml::Str(value) => std::mem::take(value),
+                Toml::Bool(value) => value.to_string(),
+                Toml::Num(value) => value.to_string(),
+                _ => {
+                    return Err(anyhow!(
+                        "toml value for inputs.{input} was not a string, bool, or number: {:?}",
+                        value
+                    ));
+                }
+            };
+            inputs.insert(input.to_string(), value);
+        }
+
+        Ok(inputs)
+    }
+
+    fn load_override(given: &mut BTreeMap<String, Toml>) -> Result<OverrideFile> {
+        let mut o = OverrideFile::default();
+
+        match given.get_mut("glob") {
+            Some(Toml::Str(glob)) => o.glob = std::mem::take(glob),
+            Some(glob) => return Err(anyhow!("toml value was not a string: {:?}", glob)),
+            None => return Err(anyhow!("[[override]] sections must have a glob")),
+        }
+        if let Some(literal_paths) = given.get_mut("literal_paths") {
+            match literal_paths {
+                Toml::Bool(literal_paths) => o.literal_paths = Some(*literal_paths),
+                _ => return Err(anyhow!("toml value was not a bool: {:?}", literal_paths)),
+            }
+        }
+        if let Some(post_processing_typ) = given.get_mut("post_processing_typ") {
+            o.post_processing_typ = Some(Self::toml_to_strs(post_processing_typ)?);
+        }
+        if let Some(compilation_extra_args) = given.get_mut("compilation_extra_args") {
+            o.compilation_extra_args = Some(Self::toml_to_strs(compilation_extra_args)?);
+        }
+        o.inputs = Self::load_inputs(given)?;
+
+        Ok(o)
+    }
+
+    fn load_passthrough_map(given: &mut BTreeMap<String, Toml>) -> Result<PassthroughMapFile> {
+        let mut map = PassthroughMapFile::default();
+
+        for (key, field) in [("from", &mut map.from), ("to", &mut map.to)] {
+            match given.get_mut(key) {
+                Some(Toml::Str(value)) => *field = std::mem::take(value),
+                Some(value) => return Err(anyhow!("toml value was not a string: {:?}", value)),
+                None => {
+                    return Err(anyhow!(
+                        "[[passthrough_map]] sections must have both from and to"
+                    ));
+                }
+            }
+        }
+
+        Ok(map)
+    }
+
+    fn get_configfile(
+        project_root: &Path,
+        profile: Option<&str>,
+        profile_required: bool,
+    ) -> Result<(ConfigFile, BTreeMap<String, ConfigSource>)> {
         let file = project_root.join(CONFIG_FNAME);
         let contents = fs::read_to_string(file)?;
-        let mut given = TomlParser::parse(&contents)?;
+        let mut given = Self::parse_toml(&contents)?;
 
         let mut config = ConfigFile::default();
 
+        if let Some(allow_unknown_keys) = given.get("allow_unknown_keys") {
+            match allow_unknown_keys {
+                Toml::Bool(allow_unknown_keys) => config.allow_unknown_keys = *allow_unknown_keys,
+                _ => {
+                    return Err(anyhow!(
+                        "toml value was not a bool: {:?}",
+                        allow_unknown_keys
+                    ));
+                }
+            }
+        }
+        if !config.allow_unknown_keys {
+            Self::validate_keys(&given, &contents)?;
+        }
+
+        let profile_fields = Self::apply_profile(&mut given, profile, profile_required)?;
+        let sources = given
+            .keys()
+            .map(|key| {
+                let source = match profile {
+                    Some(profile) if profile_fields.contains(key) => {
+                        ConfigSource::Profile(profile.to_string())
+                    }
+                    _ => ConfigSource::ConfigFile,
+                };
+                (key.clone(), source)
+            })
+            .collect();
+
         macro_rules! load_strs_field {
             ($name:ident) => {
                 if let Some($name) = given.get_mut(stringify!($name)) {
@@ -411,6 +1997,92 @@ impl Config {
         }
         load_strs_field!(file_listing_extra_args);
         load_strs_field!(compilation_extra_args);
+        load_strs_field!(exclude);
+        load_strs_field!(preserve);
+        load_strs_field!(suppress_warnings);
+        if let Some(partial_prefix) = given.get_mut("partial_prefix") {
+            match partial_prefix {
+                Toml::Str(partial_prefix) => {
+                    config.partial_prefix = Some(std::mem::take(partial_prefix))
+                }
+                // our TOML parser can't parse empty strings, so this is how to disable partials.
+                Toml::Bool(false) => config.partial_prefix = Some(String::new()),
+                _ => {
+                    return Err(anyhow!(
+                        "toml value was not a string or false: {:?}",
+                        partial_prefix
+                    ));
+                }
+            }
+        }
+        macro_rules! load_bool_field {
+            ($name:ident) => {
+                if let Some($name) = given.get_mut(stringify!($name)) {
+                    match $name {
+                        Toml::Bool($name) => config.$name = *$name,
+                        _ => return Err(anyhow!("toml value was not a bool: {:?}", $name)),
+                    }
+                }
+            };
+        }
+
+        load_bool_field!(use_ignore_files);
+        load_bool_field!(passthrough_ignore_case);
+        load_bool_field!(passthrough_skip_dotfiles);
+        load_bool_field!(prune_stale);
+        load_bool_field!(staged_builds);
+        if let Some(jobs) = given.get_mut("jobs") {
+            match jobs {
+                Toml::Num(n) if *n >= 0.0 && n.fract() == 0.0 => config.jobs = Some(*n as usize),
+                _ => {
+                    return Err(anyhow!(
+                        "toml value was not a non-negative integer: {:?}",
+                        jobs
+                    ));
+                }
+            }
+        }
+
+        macro_rules! load_path_field {
+            ($name:ident) => {
+                if let Some($name) = given.get_mut(stringify!($name)) {
+                    match $name {
+                        Toml::Str($name) => {
+                            config.$name = Some(PathBuf::from(std::mem::take($name)))
+                        }
+                        _ => return Err(anyhow!("toml value was not a string: {:?}", $name)),
+                    }
+                }
+            };
+        }
+
+        load_path_field!(content_dir);
+        load_path_field!(output_dir);
+        load_path_field!(template_dir);
+
+        config.inputs = Self::load_inputs(&mut given)?;
+        if let Some(overrides) = given.get_mut("override") {
+            let Toml::Array(overrides) = overrides else {
+                return Err(anyhow!(
+                    "override must be an array of tables, written as [[override]] sections"
+                ));
+            };
+            for o in overrides {
+                config.overrides.push(Self::load_override(o)?);
+            }
+        }
+        if let Some(maps) = given.get_mut("passthrough_map") {
+            let Toml::Array(maps) = maps else {
+                return Err(anyhow!(
+                    "passthrough_map must be an array of tables, written as [[passthrough_map]] sections"
+                ));
+            };
+            for map in maps {
+                config
+                    .passthrough_map
+                    .push(Self::load_passthrough_map(map)?);
+            }
+        }
         if let Some(disable_incremental) = given.get_mut("disable_incremental") {
             match disable_incremental {
                 Toml::Bool(disable_incremental) => {
@@ -425,14 +2097,6 @@ impl Config {
             }
         }
 
-        for arg in [] // appease rustfmt
-            .iter_mut()
-            .chain(config.init.iter_mut())
-            .chain(config.post_processing_typ.iter_mut())
-        {
-            *arg = arg.replace(PROJ_ROOT_REPLACEE, &project_root.to_string_lossy());
-        }
-
-        Ok(config)
+        Ok((config, sources))
     }
 }
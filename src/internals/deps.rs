@@ -0,0 +1,149 @@
+//! Track which files each page read when it was last compiled, so a change recompiles only the pages it affects.
+//!
+//! Typst reports the files a compilation read with `typst compile --deps`.
+
+use anyhow::{Context as _, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Pages are stored as they were compiled, and the files they read are resolved with [`key`].
+struct DepGraph {
+    /// Every file each page read, including itself.
+    deps_by_page: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+    /// Every page that read each file. The inverse of `deps_by_page`.
+    pages_by_dep: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+    /// Pages we don't know all the dependencies of, because their last compilation failed or Typst didn't report them.
+    /// A change to anything might affect them.
+    unknown: BTreeSet<PathBuf>,
+}
+
+impl DepGraph {
+    fn forget(&mut self, page: &Path) {
+        self.unknown.remove(page);
+        if let Some(old_deps) = self.deps_by_page.remove(page) {
+            for dep in old_deps {
+                if let Some(pages) = self.pages_by_dep.get_mut(&dep) {
+                    pages.remove(page);
+                    if pages.is_empty() {
+                        self.pages_by_dep.remove(&dep);
+                    }
+                }
+            }
+        }
+    }
+}
+
+static GRAPH: Mutex<DepGraph> = Mutex::new(DepGraph {
+    deps_by_page: BTreeMap::new(),
+    pages_by_dep: BTreeMap::new(),
+    unknown: BTreeSet::new(),
+});
+
+/// Whether every page has been compiled at least once, so the graph knows about all of them.
+static COMPLETE: AtomicBool = AtomicBool::new(false);
+
+/// Resolve `path` so the paths Typst reports and the paths the watcher reports agree.
+fn key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// A fresh path for `typst compile --deps` to write to.
+pub fn deps_file() -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    std::env::temp_dir().join(format!(
+        "cts-deps-{}-{}.json",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Read the files listed in a `--deps-format json` file, then remove it.
+pub fn read_deps_file(deps_file: &Path) -> Result<Vec<PathBuf>> {
+    let contents = fs::read_to_string(deps_file)
+        .context(format!("Typst didn't write dependencies to {deps_file:?}"))?;
+    fs::remove_file(deps_file)?;
+
+    let deps = json::parse(&contents).context("Typst wrote dependencies that aren't JSON.")?;
+    Ok(deps["inputs"]
+        .members()
+        .filter_map(|dep| dep.as_str())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Record that `page` compiled successfully after reading `deps`, replacing what it read before.
+pub fn record(page: &Path, deps: impl IntoIterator<Item = PathBuf>) {
+    let page = page.to_path_buf();
+    let mut deps: BTreeSet<PathBuf> = deps.into_iter().map(|dep| key(&dep)).collect();
+    deps.insert(key(&page));
+
+    let mut graph = GRAPH.lock().expect("nothing panics with the lock");
+    graph.forget(&page);
+    for dep in &deps {
+        graph
+            .pages_by_dep
+            .entry(dep.clone())
+            .or_default()
+            .insert(page.clone());
+    }
+    graph.deps_by_page.insert(page, deps);
+}
+
+/// Record that we don't know everything `page` read, e.g. because compiling it failed.
+///
+/// What it read before is kept, but it is recompiled on any change until it compiles successfully again.
+pub fn record_unknown(page: &Path) {
+    let mut graph = GRAPH.lock().expect("nothing panics with the lock");
+    graph.unknown.insert(page.to_path_buf());
+}
+
+/// Forget everything about `page`, or every page under it if it's a directory, e.g. because it was deleted.
+pub fn forget(page: &Path) {
+    let mut graph = GRAPH.lock().expect("nothing panics with the lock");
+    let pages: Vec<PathBuf> = graph
+        .deps_by_page
+        .keys()
+        .chain(&graph.unknown)
+        .filter(|known| known.starts_with(page))
+        .cloned()
+        .collect();
+    for page in pages {
+        graph.forget(&page);
+    }
+}
+
+/// Mark that every page has been compiled, so [`affected_pages`] can be trusted.
+pub fn mark_complete() {
+    COMPLETE.store(true, Ordering::Relaxed);
+}
+
+/// The pages to recompile because `changed` changed, or `None` if we don't know yet because not every page has been compiled.
+///
+/// Includes pages that read any of `changed` and pages whose dependencies are unknown.
+pub fn affected_pages(changed: &[PathBuf]) -> Option<BTreeSet<PathBuf>> {
+    if !COMPLETE.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let graph = GRAPH.lock().expect("nothing panics with the lock");
+    let mut pages = graph.unknown.clone();
+    for path in changed {
+        if let Some(dependents) = graph.pages_by_dep.get(&key(path)) {
+            pages.extend(dependents.iter().cloned());
+        }
+    }
+    Some(pages)
+}
+
+/// Whether any page read `path` when it was last compiled.
+pub fn is_dependency(path: &Path) -> bool {
+    GRAPH
+        .lock()
+        .expect("nothing panics with the lock")
+        .pages_by_dep
+        .contains_key(&key(path))
+}
@@ -0,0 +1,306 @@
+//! Parse the errors and warnings Typst reports, log them, and remember the latest ones of every page.
+//!
+//! Typst is run with `--diagnostic-format short`, which writes a line per diagnostic, like
+//! `src/index.typ:3:2: error: unknown variable: foo`, or `warning: ...` for those with no location.
+//!
+//! Many pages often have the same warning, e.g. that HTML export is experimental, so unless verbose, warnings are
+//! collected and logged once each by [`log_warnings`].
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::internals::config::Config;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        }
+    }
+
+    /// What to log diagnostics of this severity at.
+    pub fn level(self) -> log::Level {
+        match self {
+            Self::Error => log::Level::Error,
+            Self::Warning => log::Level::Warn,
+        }
+    }
+}
+
+/// An error or warning Typst reported while compiling a page.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Where the problem is, as Typst names it: relative to the project root, or like `@preview/pkg:0.1.0/lib.typ` in
+    /// a package. `None` if Typst didn't say.
+    pub file: Option<PathBuf>,
+    /// Counting from 1.
+    pub line: Option<usize>,
+    /// Counting from 1.
+    pub column: Option<usize>,
+    pub message: String,
+    /// Advice from Typst, and the calls the problem happened in, e.g. of a template.
+    pub hints: Vec<String>,
+}
+
+impl Diagnostic {
+    /// Where the problem is, like `src/index.typ:3:2`.
+    pub fn location(&self) -> Option<String> {
+        let file = self.file.as_ref()?.to_string_lossy();
+        Some(match (self.line, self.column) {
+            (Some(line), Some(column)) => format!("{file}:{line}:{column}"),
+            (Some(line), None) => format!("{file}:{line}"),
+            _ => file.to_string(),
+        })
+    }
+}
+
+/// The location and message. The alternate form, `{:#}`, adds a line per hint.
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(location) = self.location() {
+            write!(f, "{location}: ")?;
+        }
+        write!(f, "{}", self.message)?;
+        if f.alternate() {
+            for hint in &self.hints {
+                write!(f, "\n  hint: {hint}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Remove terminal color codes, which Typst writes when asked to use color.
+fn strip_ansi(s: &str) -> String {
+    let mut stripped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // skip to the end of the escape sequence, e.g. `\x1b[1;31m`.
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// A file, line, and column.
+type Location = (PathBuf, usize, usize);
+
+/// Split `path:line:column`, where `path` may contain colons itself.
+fn parse_location(location: &str) -> Option<Location> {
+    let mut parts = location.rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let file = parts.next().filter(|file| !file.is_empty())?;
+    Some((PathBuf::from(file), line, column))
+}
+
+/// A line starting a diagnostic: its severity, where it is, and its message.
+///
+/// Besides errors and warnings, Typst writes `help` lines, saying which call an error happened in.
+fn parse_line(line: &str) -> Option<(&str, Option<Location>, &str)> {
+    const SEVERITIES: [&str; 3] = ["error", "warning", "help"];
+
+    for severity in SEVERITIES {
+        if let Some(message) = line
+            .strip_prefix(severity)
+            .and_then(|rest| rest.strip_prefix(": "))
+        {
+            return Some((severity, None, message));
+        }
+    }
+
+    // the first severity after a location, since messages may mention severities too.
+    let (start, severity) = SEVERITIES
+        .iter()
+        .filter_map(|severity| Some((line.find(&format!(": {severity}: "))?, *severity)))
+        .min()?;
+    let location = parse_location(&line[..start])?;
+    let message = &line[start + severity.len() + 4..];
+    Some((severity, Some(location), message))
+}
+
+/// Parse what Typst wrote to stderr.
+///
+/// Lines that don't start a diagnostic continue the one before, so nothing Typst says is lost.
+pub fn parse(stderr: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for line in strip_ansi(stderr).lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match (parse_line(line), diagnostics.last_mut()) {
+            (Some(("help", location, message)), Some(last)) => {
+                last.hints.push(match location {
+                    Some((file, line, column)) => {
+                        format!("{}:{line}:{column}: {message}", file.to_string_lossy())
+                    }
+                    None => message.to_string(),
+                });
+            }
+            (Some((severity, location, message)), _) => {
+                let (file, line, column) = match location {
+                    Some((file, line, column)) => (Some(file), Some(line), Some(column)),
+                    None => (None, None, None),
+                };
+                diagnostics.push(Diagnostic {
+                    severity: if severity == "error" {
+                        Severity::Error
+                    } else {
+                        Severity::Warning
+                    },
+                    file,
+                    line,
+                    column,
+                    message: message.to_string(),
+                    hints: Vec::new(),
+                });
+            }
+            (None, Some(last)) => match line.trim_start().strip_prefix("hint: ") {
+                Some(hint) => last.hints.push(hint.to_string()),
+                None => {
+                    last.message.push('\n');
+                    last.message.push_str(line);
+                }
+            },
+            (None, None) => diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                file: None,
+                line: None,
+                column: None,
+                message: line.to_string(),
+                hints: Vec::new(),
+            }),
+        }
+    }
+    diagnostics
+}
+
+/// Warnings waiting for [`log_warnings`], with the files that had each.
+static UNLOGGED_WARNINGS: Mutex<BTreeMap<Diagnostic, BTreeSet<PathBuf>>> =
+    Mutex::new(BTreeMap::new());
+
+/// Parse and log what Typst wrote to stderr about `file`, leaving out warnings `config` suppresses.
+///
+/// Errors are logged right away, and so are warnings when verbose. Other warnings wait for [`log_warnings`].
+pub fn log_stderr(target: &str, file: &Path, stderr: &str, config: &Config) -> Vec<Diagnostic> {
+    let mut diagnostics = parse(stderr);
+    diagnostics.retain(|diagnostic| {
+        diagnostic.severity == Severity::Error || !config.is_warning_suppressed(&diagnostic.message)
+    });
+
+    let verbose = config.verbose || config.trace;
+    let shown_file = file.strip_prefix(&config.project_root).unwrap_or(file);
+    for diagnostic in &diagnostics {
+        if diagnostic.severity == Severity::Warning && !verbose {
+            UNLOGGED_WARNINGS
+                .lock()
+                .expect("nothing panics with the lock")
+                .entry(diagnostic.clone())
+                .or_default()
+                .insert(file.to_path_buf());
+        } else {
+            log::log!(
+                target: target,
+                diagnostic.severity.level(),
+                "in {}: {diagnostic:#}",
+                shown_file.to_string_lossy()
+            );
+        }
+    }
+    diagnostics
+}
+
+/// Log each warning collected by [`log_stderr`] once, with how many files had it.
+pub fn log_warnings(config: &Config) {
+    let warnings = std::mem::take(
+        &mut *UNLOGGED_WARNINGS
+            .lock()
+            .expect("nothing panics with the lock"),
+    );
+    for (warning, files) in warnings {
+        let first = files
+            .first()
+            .expect("warnings are collected with their file");
+        let first = first.strip_prefix(&config.project_root).unwrap_or(first);
+        if files.len() == 1 {
+            log::warn!(target: "typst", "in {}: {warning:#}", first.to_string_lossy());
+        } else {
+            log::warn!(
+                target: "typst",
+                "{warning:#}\n  emitted by {} files, including {}. Pass --verbose to see each.",
+                files.len(),
+                first.to_string_lossy()
+            );
+        }
+    }
+}
+
+/// The diagnostics of each page from the last time it was compiled, for pages that had any.
+static DIAGNOSTICS: Mutex<BTreeMap<PathBuf, Vec<Diagnostic>>> = Mutex::new(BTreeMap::new());
+
+/// Replace what's known about `page` with the diagnostics of compiling it just now.
+pub fn record(page: &Path, diagnostics: Vec<Diagnostic>) {
+    let mut all = DIAGNOSTICS.lock().expect("nothing panics with the lock");
+    if diagnostics.is_empty() {
+        all.remove(page);
+    } else {
+        all.insert(page.to_path_buf(), diagnostics);
+    }
+}
+
+/// Add to what's known about `page`, e.g. an error compiling it that Typst didn't report.
+pub fn add(page: &Path, diagnostic: Diagnostic) {
+    DIAGNOSTICS
+        .lock()
+        .expect("nothing panics with the lock")
+        .entry(page.to_path_buf())
+        .or_default()
+        .push(diagnostic);
+}
+
+/// Stop reporting diagnostics of `page`, or every page under it if it's a directory, e.g. because it was deleted.
+pub fn forget(page: &Path) {
+    DIAGNOSTICS
+        .lock()
+        .expect("nothing panics with the lock")
+        .retain(|known, _| !known.starts_with(page));
+}
+
+/// The diagnostics of `page` from the last time it was compiled.
+pub fn of(page: &Path) -> Vec<Diagnostic> {
+    DIAGNOSTICS
+        .lock()
+        .expect("nothing panics with the lock")
+        .get(page)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// The errors of every page whose last compilation failed, with the page each came from.
+pub fn errors() -> Vec<(PathBuf, Diagnostic)> {
+    DIAGNOSTICS
+        .lock()
+        .expect("nothing panics with the lock")
+        .iter()
+        .flat_map(|(page, diagnostics)| {
+            diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                .map(|diagnostic| (page.clone(), diagnostic.clone()))
+        })
+        .collect()
+}
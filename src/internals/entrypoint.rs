@@ -3,41 +3,65 @@
 use anyhow::{Context, Result, anyhow};
 use notify_debouncer_full;
 use notify_debouncer_full::DebounceEventResult;
+use notify_debouncer_full::notify::event::ModifyKind;
 use notify_debouncer_full::notify::{EventKind, RecursiveMode};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::thread;
 use std::{sync::mpsc, time::Duration};
 
 use crate::internals::compile::{self, CompileOutput};
-use crate::internals::config::Config;
+use crate::internals::config::{Config, Subcommand};
+use crate::internals::serve::{BuildError, BuildErrors};
+use crate::internals::{cache, cli_docs, deps, diagnostics, print_config, scaffold};
 
 /// Run compile-typst-site.
 ///
 /// When serving or watching, we do our best to not exit by logging errors or warnings when we might otherwise return an Error.
 pub fn run(config: &Config) -> Result<()> {
-    match Command::new("typst").arg("--version").output() {
-        Ok(typst_version) => {
-            log::info!(
-                "Using system typst with version: {}",
-                String::from_utf8(typst_version.stdout).context(
-                    "`typst --version` wrote to stdout \
-                    with bytes that could not be interpreted as UTF-8."
-                )?
-            )
+    match &config.subcommand {
+        Subcommand::Build => (),
+        Subcommand::New { dir, from } => {
+            return scaffold::new_project(config, dir, from.as_deref());
         }
+        Subcommand::Config { format } => {
+            print_config::print(config, *format);
+            return Ok(());
+        }
+        Subcommand::Clean => return cache::clean(config),
+        Subcommand::Completions { shell } => {
+            print!("{}", cli_docs::completions(*shell));
+            return Ok(());
+        }
+        Subcommand::Man => {
+            print!("{}", cli_docs::man());
+            return Ok(());
+        }
+    }
+
+    let typst_version = match Command::new("typst").arg("--version").output() {
+        Ok(typst_version) => String::from_utf8(typst_version.stdout).context(
+            "`typst --version` wrote to stdout \
+            with bytes that could not be interpreted as UTF-8.",
+        )?,
         Err(_) => {
             return Err(anyhow!(
                 "Typst doesn't seem to be installed on your computer. \
                 See https://typst.app/open-source/#download"
             ));
         }
-    }
+    };
+    log::info!("Using system typst with version: {typst_version}");
+
+    cache::load(config, typst_version.trim());
 
     log::debug!("loaded configuration: {:#?}", &config);
 
     if config.ignore_initial {
         log::info!("ignoring initial compile from scratch");
+    } else if config.watch || config.serve {
+        // keep watching, so the failures can be fixed.
+        compile::compile_from_scratch(config).unwrap_or_else(|e| log::warn!("{:?}", e));
     } else {
         compile::compile_from_scratch(&config)?;
     }
@@ -47,7 +71,9 @@ pub fn run(config: &Config) -> Result<()> {
     }
 
     let reload_tx = if config.serve {
-        let (reload_tx, reload_rx) = mpsc::channel::<()>();
+        let (reload_tx, reload_rx) = mpsc::channel::<BuildErrors>();
+        // so pages show what failed in the initial build.
+        reload_tx.send(build_errors(config))?;
 
         let path_to_site = config.output_root();
         thread::spawn(|| crate::internals::serve::serve(reload_rx, path_to_site));
@@ -61,7 +87,17 @@ pub fn run(config: &Config) -> Result<()> {
 
     let mut debouncer = notify_debouncer_full::new_debouncer(Duration::from_millis(200), None, tx)?;
     debouncer.watch(&config.project_root, RecursiveMode::Recursive)?;
+    // content, templates, and passthrough_map sources may live outside the project root if configured to.
+    let roots = [config.content_root(), config.template_root()]
+        .into_iter()
+        .chain(compile::mapped_source_roots(config));
+    for root in roots {
+        if root.strip_prefix(&config.project_root).is_err() && root.exists() {
+            debouncer.watch(&root, RecursiveMode::Recursive)?;
+        }
+    }
 
+    let generated_files = config.generated_files();
     for res in rx {
         let events = res.unwrap_or_else(|errs| {
             for err in errs {
@@ -72,56 +108,119 @@ pub fn run(config: &Config) -> Result<()> {
         });
 
         for event in events {
-            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            let kind = event.kind;
+            if !matches!(
+                kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) {
                 continue;
             }
+            let renamed = matches!(kind, EventKind::Modify(ModifyKind::Name(_)));
 
-            let file_created = matches!(event.kind, EventKind::Create(_));
-
-            let relevant_paths: Vec<PathBuf> = event
+            // renames report the old path, the new path, or both, so tell them apart by whether they still exist.
+            let (removed_paths, relevant_paths): (Vec<PathBuf>, Vec<PathBuf>) = event
                 .event
                 .paths
                 .into_iter()
                 .filter(|path| {
-                    path.strip_prefix(config.content_root()).is_ok()
+                    (path.strip_prefix(config.content_root()).is_ok()
                         || path.strip_prefix(config.template_root()).is_ok()
+                        || config.passthrough_copy_globs.mapped_destination(path).is_some()
+                        // e.g. data files read from outside the content root.
+                        || deps::is_dependency(path))
+                        // don't trigger on our own output or cache if they live under the content root.
+                        && config
+                            .generated_roots()
+                            .iter()
+                            .all(|root| path.strip_prefix(root).is_err())
+                        // e.g. a page reading `files.json` would otherwise rebuild everything each time it's rewritten.
+                        && !generated_files.contains(&std::path::absolute(path).unwrap_or_else(|_| path.clone()))
+                        && !config.exclude_globs.is_excluded(path)
                 })
-                .collect();
+                .partition(|path| {
+                    matches!(kind, EventKind::Remove(_)) || (renamed && !path.exists())
+                });
 
-            if relevant_paths.is_empty() {
+            if removed_paths.is_empty() && relevant_paths.is_empty() {
                 continue;
             }
 
-            if file_created || config.disable_incremental {
-                compile::compile_from_scratch(&config).unwrap_or_else(|e| log::warn!("{:?}", e));
-                if let Some(reload_tx) = &reload_tx {
-                    reload_tx.send(())?;
-                }
-            } else {
-                compile::compile_batch(relevant_paths.clone().into_iter(), &config)
-                    .unwrap_or_else(|e| log::warn!("{:?}", e));
+            for path in &removed_paths {
+                compile::remove_output(path, config).unwrap_or_else(|e| log::warn!("{:?}", e));
+            }
 
-                if let Some(reload_tx) = &reload_tx {
-                    for path in &relevant_paths {
-                        match CompileOutput::from_full_path(path, config)? {
-                            CompileOutput::Noop => (),
-                            _ => reload_tx
-                                .send(())
-                                .unwrap_or_else(|e| log::error!("{:?}", e)),
+            // a new or removed file may be a page, or change what `files.json` lists, so rebuild everything.
+            let file_created = matches!(kind, EventKind::Create(_)) || renamed;
+            let affected_pages =
+                if file_created || !removed_paths.is_empty() || config.disable_incremental {
+                    None
+                } else {
+                    deps::affected_pages(&relevant_paths)
+                };
+
+            if let Some(affected_pages) = affected_pages {
+                // changed pages and passthrough files are recompiled or copied themselves,
+                // and everything else that changed is only relevant to the pages that read it.
+                let mut to_compile = affected_pages;
+                for path in &relevant_paths {
+                    match CompileOutput::from_full_path(path, config)? {
+                        CompileOutput::CompileToPath(_) | CompileOutput::Passthrough(_) => {
+                            to_compile.insert(path.clone());
                         }
+                        CompileOutput::Noop | CompileOutput::RecompileAll => (),
                     }
                 }
+
+                if to_compile.is_empty() {
+                    log::info!("no page depends on changed paths: {:?}", relevant_paths);
+                    continue;
+                }
+                log::debug!("recompiling affected paths: {:?}", to_compile);
+
+                compile::compile_batch(to_compile.into_iter(), &config)
+                    .unwrap_or_else(|e| log::warn!("{:?}", e));
+            } else {
+                compile::compile_from_scratch(&config).unwrap_or_else(|e| log::warn!("{:?}", e));
+            }
+
+            if let Some(reload_tx) = &reload_tx {
+                reload_tx.send(build_errors(config))?;
             }
 
             // it might as well be an invariant that there is one event.event.paths
-            // since we watch for Create and Modify. oh well.
-            if relevant_paths.len() == 1 {
-                log::info!("recompiled path: {:?}", relevant_paths[0]);
+            // since we watch for Create, Modify, and Remove. oh well. renames may have two.
+            let changed_paths: Vec<&PathBuf> =
+                removed_paths.iter().chain(&relevant_paths).collect();
+            if changed_paths.len() == 1 {
+                log::info!("recompiled path: {:?}", changed_paths[0]);
             } else {
-                log::info!("recompiled paths: {:?}", relevant_paths);
+                log::info!("recompiled paths: {:?}", changed_paths);
             }
         }
     }
 
     Ok(())
 }
+
+/// The errors of every page whose last compilation failed, for the dev server to show.
+fn build_errors(config: &Config) -> BuildErrors {
+    diagnostics::errors()
+        .into_iter()
+        .map(|(page, diagnostic)| {
+            let url = match CompileOutput::from_full_path(&page, config) {
+                Ok(CompileOutput::CompileToPath(dst_path)) => {
+                    compile::page_url(&dst_path, config).ok()
+                }
+                _ => None,
+            };
+            let page = page
+                .strip_prefix(&config.project_root)
+                .map_or_else(|_| page.clone(), Path::to_path_buf);
+            BuildError {
+                page,
+                url,
+                diagnostic,
+            }
+        })
+        .collect()
+}
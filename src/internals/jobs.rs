@@ -0,0 +1,60 @@
+//! Run jobs on a bounded number of threads.
+
+use std::cell::Cell;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::thread;
+
+thread_local! {
+    /// Which of the threads of [`run_bounded`] this is, if any.
+    static WORKER: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Which of the threads of [`run_bounded`] is running the current job, counting from 0.
+/// `None` outside of a job.
+pub fn worker() -> Option<usize> {
+    WORKER.get()
+}
+
+/// Run `job` on every item, with at most `jobs` running at once.
+///
+/// Items are started in order, each as soon as a thread frees up, so no item waits behind ones given after it.
+/// Every item is run even if some fail. Results are returned in the same order as `items`.
+pub fn run_bounded<T, R>(items: Vec<T>, jobs: usize, job: impl Fn(T) -> R + Sync) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+{
+    let num_items = items.len();
+    let queue = Mutex::new(items.into_iter().enumerate());
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|s| {
+        for worker in 0..jobs.clamp(1, num_items.max(1)) {
+            let tx = tx.clone();
+            let (queue, job) = (&queue, &job);
+            s.spawn(move || {
+                WORKER.set(Some(worker));
+                loop {
+                    // don't hold the lock while running the job.
+                    let next = queue.lock().expect("no job panics with the lock").next();
+                    let Some((i, item)) = next else {
+                        break;
+                    };
+                    tx.send((i, job(item)))
+                        .expect("receiver outlives the scope");
+                }
+            });
+        }
+    });
+    drop(tx);
+
+    let mut results: Vec<Option<R>> = (0..num_items).map(|_| None).collect();
+    for (i, result) in rx {
+        results[i] = Some(result);
+    }
+    results
+        .into_iter()
+        .map(|result| result.expect("every item was run"))
+        .collect()
+}
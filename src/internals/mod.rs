@@ -1,5 +1,15 @@
+pub mod atomic;
+pub mod cache;
+pub mod cli_docs;
 pub mod compile;
 pub mod config;
+pub mod deps;
+pub mod diagnostics;
 pub mod entrypoint;
+pub mod jobs;
 pub mod logging;
+pub mod print_config;
+pub mod report;
+pub mod scaffold;
 pub mod serve;
+pub mod timings;
@@ -0,0 +1,218 @@
+//! Print the resolved configuration for the `config` command.
+
+use json::JsonValue;
+use std::path::Path;
+
+use crate::internals::config::{
+    CONFIG_FNAME, Config, ConfigFormat, ConfigSource, Override, PassthroughMap,
+};
+
+/// A top-level value of the resolved configuration, with where it came from.
+struct Entry {
+    key: &'static str,
+    value: JsonValue,
+    source: ConfigSource,
+}
+
+/// Print `config` to stdout in `format`.
+pub fn print(config: &Config, format: ConfigFormat) {
+    let output = match format {
+        ConfigFormat::Toml => to_toml(config),
+        ConfigFormat::Json => to_json(config).pretty(2) + "\n",
+    };
+    print!("{output}");
+}
+
+fn path_value(path: &Path) -> JsonValue {
+    path.to_string_lossy().to_string().into()
+}
+
+fn entries(config: &Config) -> Vec<Entry> {
+    let entry = |key, value: JsonValue| Entry {
+        key,
+        value,
+        source: config.source(key),
+    };
+
+    let mut entries = vec![entry("project_root", path_value(&config.project_root))];
+    if let Some(profile) = &config.profile {
+        entries.push(entry("profile", profile.as_str().into()));
+    }
+    entries.extend([
+        entry("passthrough_copy", config.passthrough_copy.clone().into()),
+        Entry {
+            key: "passthrough_copy_globs",
+            value: config.passthrough_copy_globs_string_form.clone().into(),
+            source: config.source("passthrough_copy"),
+        },
+        entry(
+            "passthrough_ignore_case",
+            config.passthrough_ignore_case.into(),
+        ),
+        entry(
+            "passthrough_skip_dotfiles",
+            config.passthrough_skip_dotfiles.into(),
+        ),
+        entry("init", config.init.clone().into()),
+        entry(
+            "post_processing_typ",
+            config.post_processing_typ.clone().into(),
+        ),
+        entry("literal_paths", config.literal_paths.into()),
+        entry("file_listing", config.file_listing.as_str().into()),
+        entry(
+            "file_listing_extra_args",
+            config.file_listing_extra_args.clone().into(),
+        ),
+        entry(
+            "compilation_extra_args",
+            config.compilation_extra_args.clone().into(),
+        ),
+        entry("disable_incremental", config.disable_incremental.into()),
+        entry("content_dir", path_value(&config.content_relpath)),
+        entry("output_dir", path_value(&config.output_relpath)),
+        entry("template_dir", path_value(&config.template_relpath)),
+        entry("exclude", config.exclude.clone().into()),
+        entry("use_ignore_files", config.use_ignore_files.into()),
+        entry("jobs", config.jobs.into()),
+        entry("prune_stale", config.prune_stale.into()),
+        entry("preserve", config.preserve.clone().into()),
+        entry("staged_builds", config.staged_builds.into()),
+        entry("suppress_warnings", config.suppress_warnings.clone().into()),
+        // an empty prefix is written as `false` in the config file, so show it the same way.
+        entry(
+            "partial_prefix",
+            if config.partial_prefix.is_empty() {
+                false.into()
+            } else {
+                config.partial_prefix.as_str().into()
+            },
+        ),
+    ]);
+
+    entries
+}
+
+fn override_value(o: &Override) -> JsonValue {
+    let mut value = JsonValue::new_object();
+    value["glob"] = o.glob.as_str().into();
+    if let Some(literal_paths) = o.literal_paths {
+        value["literal_paths"] = literal_paths.into();
+    }
+    if let Some(post_processing_typ) = &o.post_processing_typ {
+        value["post_processing_typ"] = post_processing_typ.clone().into();
+    }
+    if let Some(compilation_extra_args) = &o.compilation_extra_args {
+        value["compilation_extra_args"] = compilation_extra_args.clone().into();
+    }
+    if !o.inputs.is_empty() {
+        value["inputs"] = o.inputs.clone().into();
+    }
+    value
+}
+
+fn passthrough_map_value(map: &PassthroughMap) -> JsonValue {
+    json::object! {
+        from: map.from.as_str(),
+        to: map.to.to_string_lossy().to_string(),
+    }
+}
+
+fn to_json(config: &Config) -> JsonValue {
+    let with_source = |value: JsonValue, source: ConfigSource| {
+        json::object! { value: value, source: source.to_string() }
+    };
+
+    let mut output = JsonValue::new_object();
+    for Entry { key, value, source } in entries(config) {
+        output[key] = with_source(value, source);
+    }
+
+    let mut inputs = JsonValue::new_object();
+    for (key, value) in &config.inputs {
+        inputs[key.as_str()] = with_source(
+            value.as_str().into(),
+            config.source(&format!("inputs.{key}")),
+        );
+    }
+    output["inputs"] = inputs;
+
+    output["override"] = with_source(
+        JsonValue::Array(config.overrides.iter().map(override_value).collect()),
+        config.source("override"),
+    );
+    output["passthrough_map"] = with_source(
+        JsonValue::Array(
+            config
+                .passthrough_copy_globs
+                .maps()
+                .iter()
+                .map(passthrough_map_value)
+                .collect(),
+        ),
+        config.source("passthrough_map"),
+    );
+
+    output
+}
+
+/// Format `key` as a TOML key, quoting it if it can't be bare.
+fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+    {
+        key.to_string()
+    } else {
+        json::stringify(key)
+    }
+}
+
+/// Every value we print is a string, bool, array of strings, or table of strings, whose JSON forms are also valid TOML.
+fn to_toml(config: &Config) -> String {
+    let mut output = format!(
+        "# Resolved configuration for {}.\n# Comments say where each value came from.\n\n",
+        config.project_root.join(CONFIG_FNAME).to_string_lossy()
+    );
+
+    for Entry { key, value, source } in entries(config) {
+        output += &format!("{key} = {}  # {source}\n", json::stringify(value));
+    }
+
+    output += "\n[inputs]\n";
+    for (key, value) in &config.inputs {
+        output += &format!(
+            "{} = {}  # {}\n",
+            toml_key(key),
+            json::stringify(value.as_str()),
+            config.source(&format!("inputs.{key}"))
+        );
+    }
+
+    for o in &config.overrides {
+        output += &format!("\n[[override]]  # {}\n", config.source("override"));
+        let value = override_value(o);
+        for (key, value) in value.entries().filter(|(key, _)| *key != "inputs") {
+            output += &format!("{key} = {}\n", json::stringify(value.clone()));
+        }
+        if !o.inputs.is_empty() {
+            output += "[override.inputs]\n";
+            for (key, value) in &o.inputs {
+                output += &format!("{} = {}\n", toml_key(key), json::stringify(value.as_str()));
+            }
+        }
+    }
+
+    for map in config.passthrough_copy_globs.maps() {
+        output += &format!(
+            "\n[[passthrough_map]]  # {}\n",
+            config.source("passthrough_map")
+        );
+        for (key, value) in passthrough_map_value(map).entries() {
+            output += &format!("{key} = {}\n", json::stringify(value.clone()));
+        }
+    }
+
+    output
+}
@@ -0,0 +1,140 @@
+//! Record what a full build did with each source file, and how long it took, for `--report`.
+
+use anyhow::Result;
+use json::JsonValue;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::internals::atomic;
+use crate::internals::compile::rfc3339_now;
+use crate::internals::config::Config;
+use crate::internals::diagnostics::{Diagnostic, Severity};
+
+/// What happened to a single source file.
+#[derive(Default)]
+pub struct FileReport {
+    /// The kind of [`CompileOutput`](crate::internals::compile::CompileOutput), e.g. `compile`.
+    pub kind: &'static str,
+    /// Relative to the output root.
+    pub output: Option<PathBuf>,
+    /// `ok`, `cached`, or `failed`. `None` until the file is done.
+    pub status: Option<&'static str>,
+    pub typst: Option<Duration>,
+    pub post_processing: Option<Duration>,
+    pub output_bytes: Option<u64>,
+    pub warnings: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl FileReport {
+    /// Add the warnings and errors Typst reported.
+    pub fn add_diagnostics(&mut self, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics {
+            match diagnostic.severity {
+                Severity::Warning => self.warnings.push(diagnostic.to_string()),
+                Severity::Error => self.errors.push(diagnostic.to_string()),
+            }
+        }
+    }
+}
+
+struct Report {
+    started_at: String,
+    start: Instant,
+    /// How long each step of the build took, e.g. `init`.
+    steps: Vec<(&'static str, Duration)>,
+    files: BTreeMap<PathBuf, FileReport>,
+}
+
+/// `None` unless a full build with `--report` is running.
+static REPORT: Mutex<Option<Report>> = Mutex::new(None);
+
+/// Start recording a full build, if `config` asks for a report.
+pub fn start(config: &Config) {
+    if config.report.is_none() {
+        return;
+    }
+
+    *REPORT.lock().expect("nothing panics with the lock") = Some(Report {
+        started_at: rfc3339_now(),
+        start: Instant::now(),
+        steps: Vec::new(),
+        files: BTreeMap::new(),
+    });
+}
+
+/// Record that the step `name` of the build took `duration`.
+pub fn step(name: &'static str, duration: Duration) {
+    if let Some(report) = REPORT
+        .lock()
+        .expect("nothing panics with the lock")
+        .as_mut()
+    {
+        report.steps.push((name, duration));
+    }
+}
+
+/// Update what is recorded about the source file at `path`, if recording.
+pub fn update(path: &Path, f: impl FnOnce(&mut FileReport)) {
+    if let Some(report) = REPORT
+        .lock()
+        .expect("nothing panics with the lock")
+        .as_mut()
+    {
+        f(report.files.entry(path.to_path_buf()).or_default());
+    }
+}
+
+fn millis(duration: Duration) -> JsonValue {
+    (duration.as_secs_f64() * 1000.0).into()
+}
+
+/// Stop recording, and write the report to the path `config` asks for.
+pub fn finish(config: &Config, succeeded: bool) -> Result<()> {
+    let Some(report) = REPORT.lock().expect("nothing panics with the lock").take() else {
+        return Ok(());
+    };
+    let Some(report_path) = &config.report else {
+        return Ok(());
+    };
+
+    let mut totals = json::object! { files: report.files.len() };
+    let mut files = JsonValue::new_array();
+    for (path, file) in &report.files {
+        let status = file.status.unwrap_or("ok");
+        totals[status] = (totals[status].as_usize().unwrap_or(0) + 1).into();
+
+        let source = path.strip_prefix(&config.project_root).unwrap_or(path);
+        files.push(json::object! {
+            source: source.to_string_lossy().as_ref(),
+            kind: file.kind,
+            output: file.output.as_ref().map(|output| output.to_string_lossy().to_string()),
+            status: status,
+            typst_ms: file.typst.map(millis),
+            post_processing_ms: file.post_processing.map(millis),
+            output_bytes: file.output_bytes,
+            warnings: file.warnings.clone(),
+            errors: file.errors.clone(),
+        })?;
+    }
+
+    let mut output = json::object! {
+        started_at: report.started_at.as_str(),
+        succeeded: succeeded,
+        duration_ms: millis(report.start.elapsed()),
+        project_root: config.project_root.to_string_lossy().as_ref(),
+        output_root: config.output_root().to_string_lossy().as_ref(),
+    };
+    for (name, duration) in &report.steps {
+        output[format!("{name}_ms")] = millis(*duration);
+    }
+    output["totals"] = totals;
+    output["files"] = files;
+
+    atomic::write(report_path, output.pretty(2) + "\n")?;
+    log::info!("wrote build report to {}", report_path.to_string_lossy());
+
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! Create new `compile-typst-site` projects.
+
+use anyhow::{Context as _, Result, anyhow};
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::internals::config::{CONFIG_FNAME, Config, FileListing};
+
+/// A config file with commented-out defaults for every key, except what the scaffold needs.
+const CONFIG: &str = r#"# Configuration for compile-typst-site. Its directory is the project root.
+# Commented-out keys show their defaults. Relative directories are relative to the project root,
+# and globs are rooted in the content directory.
+
+# Globs of files to copy to the output directory as-is. Globs starting with ! exclude files again.
+passthrough_copy = ["style.css"]
+
+# Match passthrough globs case-insensitively, and don't let their wildcards match dotfiles.
+# passthrough_ignore_case = false
+# passthrough_skip_dotfiles = false
+
+# Command to run before a full rebuild.
+# init = []
+
+# Command to post-process HTML generated by Typst. Must read stdin and write to stdout.
+# post_processing_typ = []
+
+# Write content.typ to content.html instead of content/index.html.
+# literal_paths = false
+
+# Write a listing of source files to files.json. One of "disabled", "enabled", or "include-data".
+# file_listing = "disabled"
+
+# Extra arguments to `typst query` when listing files, and to `typst compile`.
+# file_listing_extra_args = []
+# compilation_extra_args = []
+
+# Rebuild the entire site on any change while watching or serving.
+# disable_incremental = false
+
+# How many Typst processes to run at once. Defaults to the available parallelism.
+# jobs = 8
+
+# After a full build, delete output files no source produces anymore, except those matching
+# preserve globs, which are rooted in the output directory.
+# prune_stale = false
+# preserve = []
+
+# Build the whole site next to the output directory, and only replace it if every file compiled.
+# staged_builds = false
+
+# Typst warnings to never show, matched against part of their message. Repeated warnings are
+# otherwise shown once, with how many files emitted them.
+# suppress_warnings = []
+
+# Where content, output, and templates live.
+# content_dir = "src"
+# output_dir = "_site"
+# template_dir = "templates"
+
+# Globs of files to pretend don't exist.
+# exclude = []
+
+# Also exclude files matching patterns in .gitignore and .ctsignore files.
+# use_ignore_files = false
+
+# Typst files starting with this prefix can be imported, but aren't compiled to pages. false disables this.
+# partial_prefix = "_"
+
+# Ignore unknown keys in this file instead of erroring.
+# allow_unknown_keys = false
+
+# Values passed to Typst as sys.inputs.
+# [inputs]
+# site_title = "My Site"
+
+# Settings for only the files matching a glob.
+# [[override]]
+# glob = "legacy/**"
+# literal_paths = true
+
+# Copy files from anywhere under the project root to a directory in the output directory.
+# [[passthrough_map]]
+# from = "node_modules/katex/dist/**"
+# to = "vendor/katex/"
+
+# Settings selected with --profile NAME. The dev profile is used while watching or serving.
+# [profile.production]
+# compilation_extra_args = ["--ignore-system-fonts"]
+"#;
+
+const BASE_TEMPLATE: &str = r#"// The template every page uses. Changes here recompile every page while watching.
+#let conf(
+  page-title: "",
+  doc,
+) = html.html[
+  #html.head[
+    #html.meta(charset: "utf-8")
+    #html.meta(name: "viewport", content: "width=device-width, initial-scale=1")
+    #html.title(page-title)
+    #html.link(rel: "stylesheet", href: "/style.css")
+  ]
+  #html.body[
+    #html.nav[
+      #html.a(href: "/")[home]
+    ]
+
+    #html.main[
+      #html.h1(page-title)
+      #doc
+    ]
+  ]
+]
+"#;
+
+const INDEX: &str = r#"#import "/templates/base.typ": conf
+
+#show: conf.with(
+  page-title: "home",
+)
+
+Welcome to your new site! Edit `src/index.typ` to change this page,
+and add more `.typ` files to `src` to add more pages.
+"#;
+
+const STYLESHEET: &str = r#"body {
+  margin: 0 auto;
+  max-width: 40em;
+  padding: 1em;
+  font-family: sans-serif;
+  line-height: 1.5;
+}
+"#;
+
+/// Create a new project in `dir`, which must not exist or be empty.
+///
+/// If `from` is given, copy that directory (except what building it generated) instead of using the built-in scaffold.
+pub fn new_project(config: &Config, dir: &Path, from: Option<&Path>) -> Result<()> {
+    if dir.exists() && fs::read_dir(dir)?.next().is_some() {
+        return Err(anyhow!(
+            "Can't create a new project in {}: it isn't empty.",
+            dir.to_string_lossy()
+        ));
+    }
+
+    match from {
+        Some(from) => copy_project(config, from, dir)?,
+        None => {
+            for (rel_path, contents) in [
+                (CONFIG_FNAME, CONFIG),
+                ("templates/base.typ", BASE_TEMPLATE),
+                ("src/index.typ", INDEX),
+                ("src/style.css", STYLESHEET),
+            ] {
+                let path = dir.join(rel_path);
+                fs::create_dir_all(path.parent().context("Found no parent.")?)?;
+                fs::write(&path, contents)
+                    .context(format!("Failed to write scaffold to {:?}", &path))?;
+                log::trace!("scaffolded {}", path.to_string_lossy());
+            }
+        }
+    }
+
+    log::info!(
+        "created a new project in {}. Run `compile-typst-site --serve` there to see it",
+        dir.to_string_lossy()
+    );
+
+    Ok(())
+}
+
+/// Recursively copy the project at `from` to `to`, skipping what building it generated, as its config file says.
+fn copy_project(config: &Config, from: &Path, to: &Path) -> Result<()> {
+    if !from.join(CONFIG_FNAME).is_file() {
+        return Err(anyhow!(
+            "Can't scaffold from {}: it has no {CONFIG_FNAME}.",
+            from.to_string_lossy()
+        ));
+    }
+
+    let from_config = config.for_project(from)?;
+    let mut generated = from_config.generated_roots().to_vec();
+    if !matches!(from_config.file_listing, FileListing::Disabled) {
+        generated.push(from_config.file_listing_path());
+    }
+    for entry in WalkDir::new(from)
+        .into_iter()
+        .filter_entry(|entry| !generated.iter().any(|path| entry.path() == path))
+    {
+        let entry = entry?;
+        let dst_path = to.join(entry.path().strip_prefix(from)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&dst_path)?;
+        } else {
+            fs::copy(entry.path(), &dst_path)
+                .context(format!("Failed to copy scaffold to {:?}", &dst_path))?;
+            log::trace!("scaffolded {}", dst_path.to_string_lossy());
+        }
+    }
+
+    Ok(())
+}
@@ -9,6 +9,9 @@
 //! - Decides when to live-reload by deferring to the user. That is, it
 //!   triggers hot reloading when a `Receiver` gets a message.
 //!
+//! - Shows the errors of the last build over the pages that failed,
+//!   instead of reloading them, until they compile again.
+//!
 //! - Focuses on short code over exhaustive spec compliance, but is intended
 //!   to be enough for the target audience of `compile-typst-site`.
 
@@ -23,13 +26,35 @@ use std::{
 
 use anyhow::{Result, anyhow};
 use bstr::ByteSlice as _;
+use json::JsonValue;
+
+use crate::internals::diagnostics::Diagnostic;
+
+/// An error of the last build, for the dev server to show.
+pub struct BuildError {
+    /// The source file it came from, relative to the project root.
+    pub page: PathBuf,
+    /// Where the page is served, if the source is one. Its errors are only shown there, and others on every page.
+    pub url: Option<String>,
+    pub diagnostic: Diagnostic,
+}
+
+/// The errors of the last build. Empty if it succeeded.
+pub type BuildErrors = Vec<BuildError>;
 
-const LIVE_RELOAD_SCRIPT: &[u8; 348] = br"<script>
+/// Reloads the page after each build, unless it failed to compile, in which case it shows why.
+const LIVE_RELOAD_SCRIPT: &[u8] = br"<script>
     const source = new EventSource('/livereload');
-    source.onmessage = () => {
-        source.close(); 
-        location.reload();
-    }
+    source.onmessage = (event) => {
+        const errors = JSON.parse(event.data);
+        // a page that failed still has its last successful build, so there's nothing new to reload.
+        if (errors.some((error) => ctsIsThisPage(error.url))) {
+            ctsShowBuildErrors(errors);
+        } else {
+            source.close();
+            location.reload();
+        }
+    };
     source.onerror = () => {
         source.close();
     };
@@ -39,6 +64,97 @@ const LIVE_RELOAD_SCRIPT: &[u8; 348] = br"<script>
     // closing must be done, lest browser complain with errors
 </script>";
 
+/// Defines `ctsShowBuildErrors`, which shows the build errors of this page, and those of no page, over it, or
+/// removes them if there are none.
+///
+/// The overlay lives in a shadow root, so the page's styles don't apply to it.
+const ERROR_OVERLAY_SCRIPT: &[u8] = br"<script>
+    // e.g. `/blog/post/`, `/blog/post`, and `/blog/post/index.html` are the same page.
+    function ctsIsThisPage(url) {
+        const normalize = (path) => path.replace(/(index)?\.html$/, '').replace(/\/$/, '');
+        return url !== null && normalize(url) === normalize(location.pathname);
+    }
+
+    function ctsShowBuildErrors(errors) {
+        document.getElementById('cts-build-errors')?.remove();
+        errors = errors.filter((error) => error.url === null || ctsIsThisPage(error.url));
+        if (errors.length === 0) {
+            return;
+        }
+
+        const host = document.createElement('div');
+        host.id = 'cts-build-errors';
+        const root = host.attachShadow({ mode: 'open' });
+        const overlay = document.createElement('div');
+        overlay.style.cssText = `
+            position: fixed; inset: 0 0 auto 0; z-index: 2147483647; max-height: 60vh; overflow: auto;
+            padding: 1em 1.5em; background: #2b0b0b; color: #fde2e2; border-bottom: 3px solid #e5484d;
+            font: 14px/1.5 ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; white-space: pre-wrap;
+        `;
+
+        const close = document.createElement('button');
+        close.textContent = '\u00d7';
+        close.title = 'Hide until the next build';
+        close.style.cssText = 'float: right; font: inherit; font-size: 1.5em; color: inherit; background: none; border: none; cursor: pointer;';
+        close.onclick = () => host.remove();
+        overlay.append(close);
+
+        const heading = document.createElement('strong');
+        heading.textContent = errors.some((error) => error.url !== null)
+            ? 'This page failed to compile, so it shows its last successful build.'
+            : 'Some files failed to compile.';
+        overlay.append(heading);
+
+        for (const error of errors) {
+            const item = document.createElement('div');
+            item.style.cssText = 'margin-top: 1em;';
+            const where = document.createElement('div');
+            where.style.cssText = 'color: #ff9b9b;';
+            where.textContent = error.location ? `${error.page}: ${error.location}` : error.page;
+            const message = document.createElement('div');
+            message.textContent = error.message;
+            item.append(where, message);
+            for (const hint of error.hints) {
+                const hintLine = document.createElement('div');
+                hintLine.style.cssText = 'color: #c9a7a7;';
+                hintLine.textContent = `hint: ${hint}`;
+                item.append(hintLine);
+            }
+            overlay.append(item);
+        }
+
+        root.append(overlay);
+        document.documentElement.append(host);
+    }
+</script>";
+
+/// The JSON `ctsShowBuildErrors` takes.
+fn build_errors_json(errors: &BuildErrors) -> String {
+    let mut json = JsonValue::new_array();
+    for BuildError {
+        page,
+        url,
+        diagnostic,
+    } in errors
+    {
+        let _infallible = json.push(json::object! {
+            page: page.to_string_lossy().as_ref(),
+            url: url.as_deref(),
+            location: diagnostic.location(),
+            line: diagnostic.line,
+            column: diagnostic.column,
+            message: diagnostic.message.as_str(),
+            hints: diagnostic.hints.clone(),
+        });
+    }
+    json.dump()
+}
+
+/// The server-sent event telling pages a build finished, with its errors.
+fn build_event(build_errors_json: &str) -> String {
+    format!("data: {build_errors_json}\r\n\r\n")
+}
+
 fn guess_mime_type(path: &PathBuf) -> &'static str {
     match path.extension().and_then(|e| e.to_str()) {
         Some(ext) if ext.eq_ignore_ascii_case("html") => "text/html; charset=utf-8",
@@ -59,13 +175,21 @@ fn guess_mime_type(path: &PathBuf) -> &'static str {
 }
 
 /// Try to inject [`LIVE_RELOAD_SCRIPT`] before `</body>`, otherwise before `</html>`, otherwise at the end.
-fn inject_livereload_script(html: &mut Vec<u8>) {
-    /// Try to inject [`LIVE_RELOAD_SCRIPT`] before some needle, returning whether that needle existed.
-    fn inject_before(html: &mut Vec<u8>, needle: &[u8]) -> bool {
+///
+/// Along with it, [`ERROR_OVERLAY_SCRIPT`] showing `build_errors_json`, so pages loaded after a failed build show it.
+fn inject_livereload_script(html: &mut Vec<u8>, build_errors_json: &str) {
+    // `</script>` in a message mustn't end the script early.
+    let build_errors_json = build_errors_json.replace("</", "<\\/");
+    let mut scripts = ERROR_OVERLAY_SCRIPT.to_vec();
+    scripts.extend(format!("<script>ctsShowBuildErrors({build_errors_json});</script>").as_bytes());
+    scripts.extend(LIVE_RELOAD_SCRIPT);
+
+    /// Try to inject `scripts` before some needle, returning whether that needle existed.
+    fn inject_before(html: &mut Vec<u8>, scripts: &[u8], needle: &[u8]) -> bool {
         if let Some(pos) = html.rfind(needle) {
             let footer = html[pos..].to_owned();
             html.truncate(pos);
-            html.extend(LIVE_RELOAD_SCRIPT);
+            html.extend(scripts);
             html.extend(footer);
             true
         } else {
@@ -73,8 +197,8 @@ fn inject_livereload_script(html: &mut Vec<u8>) {
         }
     }
 
-    if !inject_before(html, b"</body>") && !inject_before(html, b"</html>") {
-        html.extend(LIVE_RELOAD_SCRIPT);
+    if !inject_before(html, &scripts, b"</body>") && !inject_before(html, &scripts, b"</html>") {
+        html.extend(scripts);
     }
 }
 
@@ -106,6 +230,7 @@ fn handle_connection(
     stream: TcpStream,
     output_path: &PathBuf,
     hot_reload_clients: Arc<Mutex<Vec<TcpStream>>>,
+    last_build_errors_json: Arc<Mutex<String>>,
 ) -> Result<()> {
     let request_line = BufReader::new(&stream)
         .lines()
@@ -149,7 +274,7 @@ fn handle_connection(
 
         // Inject live reload script into HTML files
         if mime_type.starts_with("text/html") {
-            inject_livereload_script(&mut content);
+            inject_livereload_script(&mut content, &last_build_errors_json.lock().unwrap());
         }
 
         // Everything worked. Respond to client.
@@ -211,17 +336,25 @@ fn bind() -> Result<TcpListener> {
 
 /// Serves a simple web server that injects live-reloading capability.
 ///
-/// Triggers hot reloading when the `Receiver` gets a message.
+/// Triggers hot reloading when the `Receiver` gets the errors of a build, except on pages that failed,
+/// which show their errors instead.
 ///
 /// Blocks indefinitely.
-pub fn serve(reload_rx: Receiver<()>, path: PathBuf) -> Result<()> {
+pub fn serve(reload_rx: Receiver<BuildErrors>, path: PathBuf) -> Result<()> {
     let hot_reload_clients: Arc<Mutex<Vec<TcpStream>>> = Arc::new(Mutex::new(vec![]));
+    // for pages loaded after the build.
+    let last_build_errors_json = Arc::new(Mutex::new(String::from("[]")));
 
     let hrc = hot_reload_clients.clone();
+    let lbej = last_build_errors_json.clone();
     thread::spawn(move || {
-        for _reload_request in reload_rx {
+        for build_errors in reload_rx {
+            let json = build_errors_json(&build_errors);
+            let event = build_event(&json);
+            *lbej.lock().unwrap() = json;
+
             let mut streams = hrc.lock().unwrap();
-            streams.retain(|mut tcp_stream| tcp_stream.write_all(b"data: reload\r\n\r\n").is_ok());
+            streams.retain(|mut tcp_stream| tcp_stream.write_all(event.as_bytes()).is_ok());
             log::debug!("Tracking {} stream(s) for hot reloading.", streams.len());
         }
     });
@@ -233,10 +366,76 @@ pub fn serve(reload_rx: Receiver<()>, path: PathBuf) -> Result<()> {
             s.spawn(|| {
                 let stream = stream.unwrap();
 
-                handle_connection(stream, &path, hot_reload_clients.clone()).unwrap();
+                handle_connection(
+                    stream,
+                    &path,
+                    hot_reload_clients.clone(),
+                    last_build_errors_json.clone(),
+                )
+                .unwrap();
             });
         }
     });
 
     unreachable!()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internals::diagnostics::Severity;
+
+    fn error(file: Option<&str>, message: &str) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            file: file.map(PathBuf::from),
+            line: file.map(|_| 3),
+            column: file.map(|_| 2),
+            message: message.to_string(),
+            hints: vec!["did you mean `bar`?".to_string()],
+        }
+    }
+
+    #[test]
+    fn build_event_has_each_error_with_its_page() {
+        let errors = vec![
+            BuildError {
+                page: PathBuf::from("src/blog/post.typ"),
+                url: Some("/blog/post/".to_string()),
+                diagnostic: error(Some("templates/base.typ"), "unknown variable: foo"),
+            },
+            BuildError {
+                page: PathBuf::from("src/style.css"),
+                url: None,
+                diagnostic: error(None, "Failed to copy"),
+            },
+        ];
+
+        let event = build_event(&build_errors_json(&errors));
+        let data = event
+            .strip_prefix("data: ")
+            .and_then(|event| event.strip_suffix("\r\n\r\n"))
+            .unwrap();
+        assert!(!data.contains('\n'), "one event is one line of data");
+        let json = json::parse(data).unwrap();
+
+        assert_eq!(json.len(), 2);
+        assert_eq!(json[0]["page"], "src/blog/post.typ");
+        assert_eq!(json[0]["url"], "/blog/post/");
+        assert_eq!(json[0]["location"], "templates/base.typ:3:2");
+        assert_eq!(json[0]["line"], 3);
+        assert_eq!(json[0]["column"], 2);
+        assert_eq!(json[0]["message"], "unknown variable: foo");
+        assert_eq!(json[0]["hints"][0], "did you mean `bar`?");
+        assert!(json[1]["url"].is_null());
+        assert!(json[1]["location"].is_null());
+    }
+
+    #[test]
+    fn successful_build_event_has_no_errors() {
+        assert_eq!(
+            build_event(&build_errors_json(&Vec::new())),
+            "data: []\r\n\r\n"
+        );
+    }
+}
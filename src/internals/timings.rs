@@ -0,0 +1,276 @@
+//! Record when each process of a full build ran, and chart it for `--timings`.
+//!
+//! The chart is a self-contained HTML page, with a row per job thread, so it can be opened straight from disk.
+
+use anyhow::Result;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::internals::compile::rfc3339_now;
+use crate::internals::config::Config;
+use crate::internals::{atomic, jobs};
+
+/// How many of the slowest pages to list.
+const SLOWEST_PAGES: usize = 10;
+
+/// What a process was doing.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Init,
+    Query,
+    Compile,
+    PostProcessing,
+}
+
+impl Kind {
+    const ALL: [Kind; 4] = [Self::Init, Self::Query, Self::Compile, Self::PostProcessing];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Init => "init",
+            Self::Query => "typst query",
+            Self::Compile => "typst compile",
+            Self::PostProcessing => "post-processing",
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            Self::Init => "#9b59b6",
+            Self::Query => "#e6a23c",
+            Self::Compile => "#3b82f6",
+            Self::PostProcessing => "#10b981",
+        }
+    }
+}
+
+struct Span {
+    kind: Kind,
+    /// The source file, or for `init`, the command.
+    subject: PathBuf,
+    /// The job thread it ran on. `None` for the main thread.
+    worker: Option<usize>,
+    /// Relative to the start of the build.
+    start: Duration,
+    end: Duration,
+}
+
+struct Timings {
+    started_at: String,
+    start: Instant,
+    spans: Vec<Span>,
+}
+
+/// `None` unless a full build with `--timings` is running.
+static TIMINGS: Mutex<Option<Timings>> = Mutex::new(None);
+
+/// Start recording a full build, if `config` asks for timings.
+pub fn start(config: &Config) {
+    if !config.timings {
+        return;
+    }
+
+    *TIMINGS.lock().expect("nothing panics with the lock") = Some(Timings {
+        started_at: rfc3339_now(),
+        start: Instant::now(),
+        spans: Vec::new(),
+    });
+}
+
+/// Record that a process doing `kind` to `subject` ran from `started` to `finished`, on the current thread.
+pub fn record(kind: Kind, subject: &Path, started: Instant, finished: Instant) {
+    if let Some(timings) = TIMINGS
+        .lock()
+        .expect("nothing panics with the lock")
+        .as_mut()
+    {
+        timings.spans.push(Span {
+            kind,
+            subject: subject.to_path_buf(),
+            worker: jobs::worker(),
+            start: started.saturating_duration_since(timings.start),
+            end: finished
+                .max(started)
+                .saturating_duration_since(timings.start),
+        });
+    }
+}
+
+/// Stop recording, and write the chart to `cts-timing.html` in [`Config::timings_root`], replacing the last build's.
+pub fn finish(config: &Config, succeeded: bool) -> Result<()> {
+    let Some(timings) = TIMINGS.lock().expect("nothing panics with the lock").take() else {
+        return Ok(());
+    };
+
+    let html = render(&timings, config, succeeded);
+    let timings_root = config.timings_root();
+    fs::create_dir_all(&timings_root)?;
+    let chart = timings_root.join("cts-timing.html");
+    atomic::write(&chart, &html)?;
+    log::info!("wrote build timings to {}", chart.to_string_lossy());
+
+    Ok(())
+}
+
+fn secs(duration: Duration) -> String {
+    format!("{:.3}s", duration.as_secs_f64())
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render(timings: &Timings, config: &Config, succeeded: bool) -> String {
+    let total = timings.start.elapsed();
+    let subject = |span: &Span| {
+        let subject = span
+            .subject
+            .strip_prefix(&config.project_root)
+            .unwrap_or(&span.subject);
+        escape(&subject.to_string_lossy())
+    };
+
+    let mut html = String::new();
+    html.push_str(concat!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n",
+        "<title>compile-typst-site build timings</title>\n<style>\n",
+        "body { font-family: sans-serif; margin: 2em; color: #222; }\n",
+        "table { border-collapse: collapse; margin-bottom: 2em; }\n",
+        "th, td { text-align: left; padding: 0.2em 1em 0.2em 0; }\n",
+        "td.num { text-align: right; font-variant-numeric: tabular-nums; }\n",
+        ".legend span { display: inline-block; padding: 0.1em 0.6em; margin-right: 0.5em; color: white; }\n",
+        ".chart { margin: 1em 0 2em; }\n",
+        ".row { display: flex; align-items: center; height: 1.6em; }\n",
+        ".lane { width: 6em; flex: none; font-size: 0.85em; }\n",
+        ".track { position: relative; flex: 1; height: 1.3em; background: #f3f3f3; }\n",
+        ".axis { position: relative; flex: 1; height: 1.3em; font-size: 0.75em; color: #666; }\n",
+        ".axis span { position: absolute; transform: translateX(-50%); }\n",
+        ".span { position: absolute; top: 0; bottom: 0; min-width: 1px; opacity: 0.85; }\n",
+        ".span:hover { opacity: 1; outline: 1px solid #222; }\n",
+        "</style>\n</head>\n<body>\n",
+        "<h1>compile-typst-site build timings</h1>\n",
+    ));
+
+    let _ = write!(
+        html,
+        "<table>\n<tr><th>Project</th><td>{}</td></tr>\n<tr><th>Started</th><td>{}</td></tr>\n\
+        <tr><th>Succeeded</th><td>{}</td></tr>\n<tr><th>Total time</th><td>{}</td></tr>\n\
+        <tr><th>Jobs</th><td>{}</td></tr>\n<tr><th>Processes</th><td>{}</td></tr>\n",
+        escape(&config.project_root.to_string_lossy()),
+        timings.started_at,
+        if succeeded { "yes" } else { "no" },
+        secs(total),
+        config.jobs,
+        timings.spans.len(),
+    );
+    for kind in Kind::ALL {
+        let (count, busy) = timings
+            .spans
+            .iter()
+            .filter(|span| span.kind == kind)
+            .fold((0, Duration::ZERO), |(count, busy), span| {
+                (count + 1, busy + (span.end - span.start))
+            });
+        if count > 0 {
+            let _ = writeln!(
+                html,
+                "<tr><th>{}</th><td>{count} taking {} in total</td></tr>",
+                kind.name(),
+                secs(busy),
+            );
+        }
+    }
+    html.push_str("</table>\n\n<h2>Processes</h2>\n<div class=\"legend\">");
+    for kind in Kind::ALL {
+        let _ = write!(
+            html,
+            "<span style=\"background: {}\">{}</span>",
+            kind.color(),
+            kind.name()
+        );
+    }
+    html.push_str("</div>\n<div class=\"chart\">\n");
+
+    // the main thread first, then each job thread.
+    let mut lanes: Vec<Option<usize>> = timings.spans.iter().map(|span| span.worker).collect();
+    lanes.sort();
+    lanes.dedup();
+    let percent = |at: Duration| 100.0 * at.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON);
+    for lane in lanes {
+        let _ = write!(
+            html,
+            "<div class=\"row\"><div class=\"lane\">{}</div><div class=\"track\">",
+            lane.map_or_else(
+                || "main".to_string(),
+                |worker| format!("job {}", worker + 1)
+            ),
+        );
+        for span in timings.spans.iter().filter(|span| span.worker == lane) {
+            let _ = write!(
+                html,
+                "<div class=\"span\" style=\"left: {:.3}%; width: {:.3}%; background: {}\" title=\"{}: {} ({}, at {})\"></div>",
+                percent(span.start),
+                percent(span.end - span.start),
+                span.kind.color(),
+                span.kind.name(),
+                subject(span),
+                secs(span.end - span.start),
+                secs(span.start),
+            );
+        }
+        html.push_str("</div></div>\n");
+    }
+    html.push_str("<div class=\"row\"><div class=\"lane\"></div><div class=\"axis\">");
+    for tick in 0..=10 {
+        let _ = write!(
+            html,
+            "<span style=\"left: {}%\">{}</span>",
+            tick * 10,
+            secs(total * tick / 10)
+        );
+    }
+    html.push_str("</div></div>\n</div>\n\n");
+
+    // a page's post-processing runs right after it compiles, on the same thread.
+    let mut pages: Vec<(&Span, Option<&Span>)> = timings
+        .spans
+        .iter()
+        .filter(|span| span.kind == Kind::Compile)
+        .map(|compile| {
+            let post_processing = timings
+                .spans
+                .iter()
+                .find(|span| span.kind == Kind::PostProcessing && span.subject == compile.subject);
+            (compile, post_processing)
+        })
+        .collect();
+    let page_total = |(compile, post_processing): &(&Span, Option<&Span>)| {
+        post_processing.map_or(compile.end, |span| span.end) - compile.start
+    };
+    pages.sort_by_key(|page| std::cmp::Reverse(page_total(page)));
+
+    let _ = write!(
+        html,
+        "<h2>Slowest pages</h2>\n<table>\n\
+        <tr><th>Page</th><th>typst compile</th><th>post-processing</th><th>Total</th></tr>\n"
+    );
+    for page @ (compile, post_processing) in pages.iter().take(SLOWEST_PAGES) {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+            subject(compile),
+            secs(compile.end - compile.start),
+            post_processing.map_or_else(String::new, |span| secs(span.end - span.start)),
+            secs(page_total(page)),
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+
+    html
+}
@@ -0,0 +1 @@
+= Reads nothing
@@ -0,0 +1,3 @@
+= Profiles
+
+The production profile also copies `robots.txt`.
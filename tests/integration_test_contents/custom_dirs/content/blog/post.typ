@@ -0,0 +1,4 @@
+#import "/layouts/base.typ": conf
+#show: conf
+
+= A Post
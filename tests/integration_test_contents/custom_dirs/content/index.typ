@@ -0,0 +1,6 @@
+#import "/layouts/base.typ": conf
+#show: conf
+
+= Custom Directories
+
+Content lives in `content/`, and the site is written to `public/`.
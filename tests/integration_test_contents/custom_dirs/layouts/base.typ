@@ -0,0 +1,6 @@
+#let conf(doc) = {
+  html.elem("html", {
+    html.elem("head", html.elem("link", attrs: (rel: "stylesheet", href: "/style.css")))
+    html.elem("body", doc)
+  })
+}
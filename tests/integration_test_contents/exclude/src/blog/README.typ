@@ -0,0 +1 @@
+#read("./doesnt-exist")
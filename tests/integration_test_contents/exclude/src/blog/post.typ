@@ -0,0 +1 @@
+= A Post
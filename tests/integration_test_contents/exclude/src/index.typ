@@ -0,0 +1 @@
+= Exclude
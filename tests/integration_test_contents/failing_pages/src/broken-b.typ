@@ -0,0 +1 @@
+#doesnt-exist
@@ -0,0 +1 @@
+= Fine
@@ -0,0 +1,3 @@
+= #sys.inputs.site_title
+
+Built with build mode #sys.inputs.build_mode.
@@ -0,0 +1 @@
+= Interpolation
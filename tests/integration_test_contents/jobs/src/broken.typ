@@ -0,0 +1,3 @@
+= Broken
+
+#doesnt-exist
@@ -0,0 +1 @@
+= Page 5
@@ -0,0 +1,3 @@
+= post
+
+Section: #sys.inputs.section
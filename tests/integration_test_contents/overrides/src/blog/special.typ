@@ -0,0 +1,3 @@
+= special
+
+Section: #sys.inputs.section
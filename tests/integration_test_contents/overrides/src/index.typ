@@ -0,0 +1,3 @@
+= index
+
+Section: #sys.inputs.section
@@ -0,0 +1,3 @@
+= old
+
+Section: #sys.inputs.section
@@ -0,0 +1,9 @@
+= A Post
+
+#("url=" + sys.inputs.at("cts-url") + ";")
+
+#("source=" + sys.inputs.at("cts-source-path") + ";")
+
+#("output=" + sys.inputs.at("cts-output-path") + ";")
+
+#("built=" + sys.inputs.at("cts-build-time") + ";")
@@ -0,0 +1,5 @@
+= Home
+
+#("url=" + sys.inputs.at("cts-url") + ";")
+
+#("output=" + sys.inputs.at("cts-output-path") + ";")
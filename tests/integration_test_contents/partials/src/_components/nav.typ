@@ -0,0 +1 @@
+#let nav = [Home | Blog]
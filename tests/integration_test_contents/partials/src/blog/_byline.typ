@@ -0,0 +1 @@
+#let byline(author) = [Written by #author.]
@@ -0,0 +1,7 @@
+#import "/src/_components/nav.typ": nav
+#import "_byline.typ": byline
+#nav
+
+= A Post
+
+#byline("Someone")
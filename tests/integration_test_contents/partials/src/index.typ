@@ -0,0 +1,4 @@
+#import "_components/nav.typ": nav
+#nav
+
+= Partials
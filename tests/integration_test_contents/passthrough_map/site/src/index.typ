@@ -0,0 +1,3 @@
+= Mapped Passthrough
+
+The assets come from outside `src`.
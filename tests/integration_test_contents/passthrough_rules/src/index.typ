@@ -0,0 +1 @@
+= Passthrough Rules
@@ -0,0 +1 @@
+= Page one
@@ -0,0 +1 @@
+= Another page
@@ -0,0 +1 @@
+= Unknown Keys
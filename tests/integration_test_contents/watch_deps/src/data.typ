@@ -0,0 +1,3 @@
+#let data = json("/data.json")
+
+= #data.title
@@ -0,0 +1,5 @@
+#import "/templates/base.typ": conf
+
+#show: conf
+
+= Templated
@@ -0,0 +1,3 @@
+#let conf(doc) = [
+  #doc
+]
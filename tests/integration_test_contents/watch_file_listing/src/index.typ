@@ -0,0 +1,5 @@
+#let files = json("/files.json")
+
+= Index
+
+#files.len() files
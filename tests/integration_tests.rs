@@ -1,10 +1,10 @@
 mod util;
-use std::{env, fs, io, path::PathBuf, time::Duration};
+use std::{env, fs, io, path::PathBuf, thread, time::Duration};
 
 use compile_typst_site::internals::config::CONFIG_FNAME;
 use walkdir::WalkDir;
 
-use crate::util::IntegrationTest;
+use crate::util::{IntegrationTest, RemoveOnDrop, RestoreOnDrop};
 
 #[test]
 fn blank_project_does_nothing() {
@@ -181,3 +181,1000 @@ fn can_read_and_post_process_lengthy_content() {
 
     assert!(output.status.success());
 }
+
+#[test]
+fn custom_dirs_are_respected() {
+    let (project_root, output) = IntegrationTest::new("custom_dirs")
+        .output_dir("public")
+        .run()
+        .unwrap();
+    let output_root = project_root.join("public");
+
+    println!("stdout: {}", String::from_utf8(output.stdout).unwrap());
+    println!("stderr: {}", String::from_utf8(output.stderr).unwrap());
+
+    assert!(output.status.success());
+    assert!(output_root.join("index.html").is_file());
+    assert!(output_root.join("blog/post/index.html").is_file());
+    assert!(output_root.join("style.css").is_file());
+}
+
+#[test]
+fn cli_dirs_override_config() {
+    let (project_root, output) = IntegrationTest::new("custom_dirs")
+        .args(vec!["--out".to_string(), "public_from_cli".to_string()])
+        .output_dir("public_from_cli")
+        .run()
+        .unwrap();
+    let output_root = project_root.join("public_from_cli");
+
+    assert!(output.status.success());
+    assert!(output_root.join("index.html").is_file());
+    assert!(output_root.join("style.css").is_file());
+}
+
+#[test]
+fn profile_overrides_config() {
+    let (project_root, output) = IntegrationTest::new("profiles")
+        .output_dir("_site_production")
+        .args(vec![
+            "--profile".to_string(),
+            "production".to_string(),
+            "--out".to_string(),
+            "_site_production".to_string(),
+        ])
+        .run()
+        .unwrap();
+    let output_root = project_root.join("_site_production");
+
+    assert!(output.status.success());
+    assert!(output_root.join("style.css").is_file());
+    assert!(output_root.join("robots.txt").is_file());
+}
+
+#[test]
+fn no_profile_uses_base_config() {
+    let (project_root, output) = IntegrationTest::new("profiles").run().unwrap();
+    let output_root = project_root.join("_site");
+
+    assert!(output.status.success());
+    assert!(output_root.join("style.css").is_file());
+    assert!(!output_root.join("robots.txt").exists());
+}
+
+#[test]
+fn missing_profile_causes_error() {
+    let (_, output) = IntegrationTest::new("profiles")
+        .args(vec!["--profile".to_string(), "nonexistent".to_string()])
+        .output_dir("_site_nonexistent")
+        .run()
+        .unwrap();
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn variables_are_interpolated() {
+    let (project_root, output) = IntegrationTest::new("interpolation").run().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(project_root.join("_site/generated.txt")).unwrap(),
+        "fallback costs $5"
+    );
+}
+
+#[test]
+fn unknown_variable_causes_error() {
+    for profile in ["unknown_variable", "unset_env"] {
+        let (_, output) = IntegrationTest::new("interpolation")
+            .args(vec!["--profile".to_string(), profile.to_string()])
+            .output_dir(&format!("_site_{profile}"))
+            .run()
+            .unwrap();
+
+        assert!(!output.status.success());
+    }
+}
+
+#[test]
+fn unknown_keys_cause_error_with_suggestions() {
+    let (_, output) = IntegrationTest::new("unknown_keys").run().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    println!("stdout: {}", stdout);
+
+    assert!(!output.status.success());
+    assert!(stdout.contains("`passthru_copy` on line 2. Did you mean `passthrough_copy`?"));
+    assert!(stdout.contains(
+        "`profile.production.post_processing` on line 5. Did you mean `post_processing_typ`?"
+    ));
+    assert!(stdout.contains(
+        "`override.init` on line 9. `init` can't be set per file. Overrides only support \
+        `literal_paths`, `post_processing_typ`, `compilation_extra_args`, `inputs`"
+    ));
+}
+
+#[test]
+fn unknown_keys_can_be_allowed() {
+    let (_, output) = IntegrationTest::new("unknown_keys_allowed").run().unwrap();
+
+    assert!(output.status.success());
+}
+
+#[test]
+fn inputs_are_passed_to_typst() {
+    let (project_root, output) = IntegrationTest::new("inputs")
+        .args(vec![
+            "--input".to_string(),
+            "build_mode=from-cli".to_string(),
+        ])
+        .run()
+        .unwrap();
+    let html = fs::read_to_string(project_root.join("_site/index.html")).unwrap();
+
+    assert!(output.status.success());
+    assert!(html.contains("Inputs Site"));
+    assert!(html.contains("from-cli"));
+    assert!(!html.contains("from-config"));
+}
+
+#[test]
+fn configured_mode_input_beats_watching() {
+    let (_, output) = IntegrationTest::new("mode_input")
+        .args(vec!["config".to_string(), "--watch".to_string()])
+        .run()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.contains("cts-mode = \"preview\"  # config file"));
+}
+
+#[test]
+fn page_inputs_describe_the_page() {
+    let (project_root, output) = IntegrationTest::new("page_inputs").run().unwrap();
+    let index = fs::read_to_string(project_root.join("_site/index.html")).unwrap();
+    let post = fs::read_to_string(project_root.join("_site/blog/post/index.html")).unwrap();
+
+    assert!(output.status.success());
+    assert!(index.contains("url=/;"));
+    assert!(index.contains("output=index.html;"));
+    assert!(post.contains("url=/blog/post/;"));
+    assert!(post.contains("source=blog/post.typ;"));
+    assert!(post.contains("output=blog/post/index.html;"));
+    let built = post.split("built=").nth(1).unwrap();
+    // e.g. 2025-12-02T09:30:00Z
+    assert_eq!(built.find(';'), Some(20));
+    assert!(built.starts_with("20") && built[..20].ends_with('Z'));
+}
+
+#[test]
+fn overrides_apply_to_matching_files() {
+    let (project_root, output) = IntegrationTest::new("overrides").run().unwrap();
+    let output_root = project_root.join("_site");
+    let read = |path: &str| fs::read_to_string(output_root.join(path)).unwrap();
+
+    println!("stdout: {}", String::from_utf8(output.stdout).unwrap());
+
+    assert!(output.status.success());
+    assert!(read("index.html").contains("main"));
+    assert!(read("legacy/old.html").contains("main"));
+    assert!(read("blog/post/index.html").contains("blog"));
+    assert!(read("blog/special/index.html").contains("special"));
+    assert!(!output_root.join("legacy/old/index.html").exists());
+}
+
+#[test]
+fn excluded_files_are_ignored() {
+    let (project_root, output) = IntegrationTest::new("exclude").run().unwrap();
+    let output_root = project_root.join("_site");
+
+    println!("stdout: {}", String::from_utf8(output.stdout).unwrap());
+
+    let mut files: Vec<PathBuf> = WalkDir::new(&output_root)
+        .into_iter()
+        .map(|dir_entry| dir_entry.unwrap())
+        .filter(|dir_entry| dir_entry.metadata().unwrap().is_file())
+        .map(|dir_entry| {
+            dir_entry
+                .into_path()
+                .strip_prefix(&output_root)
+                .unwrap()
+                .to_path_buf()
+        })
+        .collect();
+    files.sort();
+
+    assert!(output.status.success());
+    assert_eq!(
+        files,
+        [
+            ["blog", "post", "index.html"].iter().collect::<PathBuf>(),
+            ["index.html"].iter().collect::<PathBuf>(),
+            ["notes", "keep.txt"].iter().collect::<PathBuf>(),
+            ["style.css"].iter().collect::<PathBuf>(),
+        ]
+    );
+
+    let listing = fs::read_to_string(project_root.join("files.json")).unwrap();
+    assert!(!listing.contains("README"));
+    assert!(!listing.contains("scratch"));
+}
+
+#[test]
+fn partials_are_not_compiled() {
+    let (project_root, output) = IntegrationTest::new("partials").run().unwrap();
+    let output_root = project_root.join("_site");
+
+    assert!(output.status.success());
+    assert!(output_root.join("index.html").is_file());
+    assert!(output_root.join("blog/post/index.html").is_file());
+    assert!(!output_root.join("_components").exists());
+    assert!(!output_root.join("blog/_byline").exists());
+}
+
+#[test]
+fn disabled_partials_compiles_everything() {
+    let (project_root, output) = IntegrationTest::new("partials")
+        .args(vec![
+            "--profile".to_string(),
+            "no_partials".to_string(),
+            "--out".to_string(),
+            "_site_no_partials".to_string(),
+        ])
+        .output_dir("_site_no_partials")
+        .run()
+        .unwrap();
+    let output_root = project_root.join("_site_no_partials");
+
+    assert!(output.status.success());
+    assert!(output_root.join("_components/nav/index.html").is_file());
+    assert!(output_root.join("blog/_byline/index.html").is_file());
+}
+
+#[test]
+fn new_project_builds() {
+    let project_root = env::current_dir()
+        .unwrap()
+        .join("tests/integration_test_contents/scaffolded");
+    let _dont_validate_removal = fs::remove_dir_all(&project_root);
+    let _cleanup = RemoveOnDrop(project_root.clone());
+
+    let (_, output) = IntegrationTest::new("scaffolded")
+        .args(vec!["new".to_string()])
+        .run()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(project_root.join(CONFIG_FNAME).is_file());
+
+    let (_, output) = IntegrationTest::new("scaffolded").run().unwrap();
+    let output_root = project_root.join("_site");
+
+    assert!(output.status.success());
+    assert!(output_root.join("index.html").is_file());
+    assert!(output_root.join("style.css").is_file());
+
+    // refuses to scaffold over an existing project.
+    let (_, output) = IntegrationTest::new("scaffolded")
+        .args(vec!["new".to_string()])
+        .run()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn new_project_from_template() {
+    let contents_root = env::current_dir()
+        .unwrap()
+        .join("tests/integration_test_contents");
+    let project_root = contents_root.join("scaffolded_from");
+    let _dont_validate_removal = fs::remove_dir_all(&project_root);
+    let _cleanup = RemoveOnDrop(project_root.clone());
+
+    // build the template first, so there's output, a cache, timings, and a file listing to leave out.
+    let (template_root, output) = IntegrationTest::new("scaffold_source")
+        .args(vec!["--timings".to_string()])
+        .output_dir("public")
+        .run()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(template_root.join("public/index.html").is_file());
+    assert!(template_root.join(".cts-cache").is_dir());
+    assert!(template_root.join("cts-timings").is_dir());
+    assert!(template_root.join("files.json").is_file());
+
+    let (_, output) = IntegrationTest::new("scaffolded_from")
+        .args(vec![
+            "new".to_string(),
+            "--from".to_string(),
+            template_root.to_string_lossy().to_string(),
+        ])
+        .run()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(project_root.join(CONFIG_FNAME)).unwrap(),
+        fs::read_to_string(template_root.join(CONFIG_FNAME)).unwrap()
+    );
+    assert!(project_root.join("src/index.typ").is_file());
+    for generated in ["public", ".cts-cache", "cts-timings", "files.json"] {
+        assert!(
+            !project_root.join(generated).exists(),
+            "{generated} was copied"
+        );
+    }
+}
+
+#[test]
+fn config_command_prints_sources_as_json() {
+    // a fixture of its own, so no other test builds into its output directory.
+    let (project_root, output) = IntegrationTest::new("config_command")
+        .args(vec![
+            "config".to_string(),
+            "--format".to_string(),
+            "json".to_string(),
+            "--profile".to_string(),
+            "production".to_string(),
+            "--input".to_string(),
+            "key=value".to_string(),
+        ])
+        .run()
+        .unwrap();
+
+    assert!(output.status.success());
+    let config = json::parse(str::from_utf8(&output.stdout).unwrap()).unwrap();
+
+    assert_eq!(
+        config["project_root"]["value"],
+        project_root.to_string_lossy().as_ref()
+    );
+    assert_eq!(config["project_root"]["source"], "command line");
+    assert_eq!(config["passthrough_copy"]["value"][1], "robots.txt");
+    assert_eq!(config["passthrough_copy"]["source"], "profile production");
+    assert_eq!(config["compilation_extra_args"]["source"], "config file");
+    assert_eq!(config["literal_paths"]["source"], "default");
+    assert_eq!(config["inputs"]["key"]["value"], "value");
+    assert_eq!(config["inputs"]["key"]["source"], "command line");
+    // nothing is built.
+    assert!(!project_root.join("_site").exists());
+}
+
+#[test]
+fn config_command_prints_toml() {
+    let (_, output) = IntegrationTest::new("overrides")
+        .args(vec!["config".to_string()])
+        .run()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(
+        stdout.contains("compilation_extra_args = [\"--ignore-system-fonts\"]  # config file\n")
+    );
+    assert!(stdout.contains("section = \"main\"  # config file\n"));
+    assert_eq!(stdout.matches("[[override]]").count(), 3);
+}
+
+#[test]
+fn completions_cover_every_option() {
+    for shell in ["bash", "zsh", "fish"] {
+        let (_, output) = IntegrationTest::new("simple")
+            .args(vec!["completions".to_string(), shell.to_string()])
+            .run()
+            .unwrap();
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        for expected in ["watch", "color", "input", "profile", "completions"] {
+            assert!(stdout.contains(expected), "{shell} is missing {expected}");
+        }
+        assert!(
+            stdout.contains("auto always never"),
+            "{shell} is missing color values"
+        );
+    }
+
+    let (_, output) = IntegrationTest::new("simple")
+        .args(vec!["completions".to_string(), "powershell".to_string()])
+        .run()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn man_page_covers_every_option() {
+    let (_, output) = IntegrationTest::new("simple")
+        .args(vec!["man".to_string()])
+        .run()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(".TH COMPILE-TYPST-SITE 1"));
+    assert!(stdout.contains("\\fB\\-c\\fR, \\fB\\-\\-color\\fR \\fISTRING\\fR"));
+    assert!(stdout.contains("possible values: auto, always, never"));
+    assert!(stdout.contains("\\fBcompletions\\fR \\fISHELL\\fR"));
+}
+
+#[test]
+fn passthrough_map_copies_from_outside_content() {
+    let (project_root, output) = IntegrationTest::new("passthrough_map/site").run().unwrap();
+    let output_root = project_root.join("_site");
+
+    assert!(output.status.success());
+    assert!(output_root.join("index.html").is_file());
+    assert!(output_root.join("style.css").is_file());
+    assert!(output_root.join("vendor/katex/katex.js").is_file());
+    assert!(
+        output_root
+            .join("vendor/katex/fonts/KaTeX_Main.woff2")
+            .is_file()
+    );
+    assert!(output_root.join("assets/logo.svg").is_file());
+    assert_eq!(
+        fs::read_to_string(output_root.join("vendor/katex.css")).unwrap(),
+        "body { color: black; }\n"
+    );
+}
+
+#[test]
+fn passthrough_negation_and_match_options() {
+    let (project_root, output) = IntegrationTest::new("passthrough_rules").run().unwrap();
+    let output_root = project_root.join("_site");
+
+    assert!(output.status.success());
+    assert!(output_root.join("assets/logo.png").is_file());
+    assert!(output_root.join("assets/sub/banner.png").is_file());
+    assert!(!output_root.join("assets/logo.psd").exists());
+    assert!(!output_root.join("assets/sub/banner.psd").exists());
+    assert!(!output_root.join("assets/.DS_Store").exists());
+    assert!(output_root.join("Photo.JPG").is_file());
+}
+
+#[test]
+fn passthrough_default_match_options() {
+    let (project_root, output) = IntegrationTest::new("passthrough_rules")
+        .args(vec![
+            "--profile".to_string(),
+            "defaults".to_string(),
+            "--out".to_string(),
+            "_site_defaults".to_string(),
+        ])
+        .output_dir("_site_defaults")
+        .run()
+        .unwrap();
+    let output_root = project_root.join("_site_defaults");
+
+    assert!(output.status.success());
+    assert!(output_root.join("assets/logo.png").is_file());
+    assert!(!output_root.join("assets/logo.psd").exists());
+    assert!(output_root.join("assets/.DS_Store").is_file());
+    assert!(!output_root.join("Photo.JPG").exists());
+}
+
+#[test]
+fn bounded_jobs_still_compile_every_page() {
+    let (project_root, output) = IntegrationTest::new("jobs")
+        .args(vec!["--keep-going".to_string()])
+        .run()
+        .unwrap();
+    let output_root = project_root.join("_site");
+
+    // broken.typ fails, but doesn't stop the other pages.
+    assert!(!output.status.success());
+    for i in 1..=5 {
+        assert!(output_root.join(format!("page{i}/index.html")).is_file());
+    }
+    assert!(!output_root.join("broken/index.html").exists());
+}
+
+#[test]
+fn jobs_flag_overrides_config() {
+    let (_, output) = IntegrationTest::new("jobs")
+        .args(vec![
+            "config".to_string(),
+            "--format".to_string(),
+            "json".to_string(),
+            "--jobs".to_string(),
+            "1".to_string(),
+        ])
+        // nothing is built, so leave the output of `bounded_jobs_still_compile_every_page` alone.
+        .keep_output()
+        .run()
+        .unwrap();
+
+    assert!(output.status.success());
+    let config = json::parse(str::from_utf8(&output.stdout).unwrap()).unwrap();
+    assert_eq!(config["jobs"]["value"], 1);
+    assert_eq!(config["jobs"]["source"], "command line");
+
+    let (_, output) = IntegrationTest::new("jobs")
+        .args(vec![
+            "config".to_string(),
+            "--jobs".to_string(),
+            "0".to_string(),
+        ])
+        .run()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn cache_skips_unchanged_pages() {
+    let run = |args: &[&str], keep_output| {
+        let test =
+            IntegrationTest::new("cache").args(args.iter().map(|arg| arg.to_string()).collect());
+        let test = if keep_output {
+            test.keep_output()
+        } else {
+            test
+        };
+        let (project_root, output) = test.run().unwrap();
+        assert!(output.status.success());
+        (project_root, String::from_utf8(output.stdout).unwrap())
+    };
+
+    let (project_root, _) = run(&["clean"], false);
+    assert!(!project_root.join(".cts-cache").exists());
+
+    // a fresh build has nothing to skip, but fills the cache.
+    let (_, stdout) = run(&[], false);
+    assert!(!stdout.contains("skipped"));
+    assert!(project_root.join(".cts-cache").is_dir());
+
+    let (_, stdout) = run(&[], true);
+    assert!(stdout.contains("skipped 2 pages"));
+
+    // pages whose output is gone are compiled again.
+    let (_, stdout) = run(&[], false);
+    assert!(!stdout.contains("skipped"));
+
+    // only the page that read the changed file is compiled again.
+    let data_path = project_root.join("data.json");
+    let data = RestoreOnDrop::new(data_path.clone());
+    fs::write(&data_path, "{\"title\": \"changed\"}\n").unwrap();
+    let (_, stdout) = run(&[], true);
+    drop(data);
+    assert!(stdout.contains("skipped 1 pages"));
+
+    // changing it back only compiles that page again too.
+    let (_, stdout) = run(&[], true);
+    assert!(stdout.contains("skipped 1 pages"));
+
+    // and so is the page whose post-processing script changed.
+    let script_path = project_root.join("post-process.sh");
+    let script = RestoreOnDrop::new(script_path.clone());
+    fs::write(&script_path, "#!/bin/sh\n# changed\ncat\n").unwrap();
+    let (_, stdout) = run(&[], true);
+    drop(script);
+    assert!(stdout.contains("skipped 1 pages"));
+
+    let (_, stdout) = run(&["--no-cache"], true);
+    assert!(!stdout.contains("skipped"));
+
+    run(&["clean"], false);
+    assert!(!project_root.join(".cts-cache").exists());
+}
+
+#[test]
+fn watching_rebuilds_only_dependents() {
+    let test = IntegrationTest::new("watch_deps")
+        .args(vec!["--watch".to_string(), "--no-cache".to_string()])
+        .timeout(Duration::from_secs(15));
+    let project_root = env::current_dir()
+        .unwrap()
+        .join("tests/integration_test_contents/watch_deps");
+    let output_root = project_root.join("_site");
+    let pages = ["templated", "data", "standalone"];
+    let modified = || {
+        pages.map(|page| {
+            fs::metadata(output_root.join(page).join("index.html"))
+                .and_then(|metadata| metadata.modified())
+                .unwrap()
+        })
+    };
+
+    let watching = test.spawn().unwrap();
+    watching.wait_for("compiled project from scratch", 1);
+    let built = modified();
+
+    // a template only rebuilds the pages that import it.
+    let _template = RestoreOnDrop::new(project_root.join("templates/base.typ"));
+    fs::write(
+        project_root.join("templates/base.typ"),
+        "#let conf(doc) = [\n  _templated_\n  #doc\n]\n",
+    )
+    .unwrap();
+    watching.wait_for("recompiled path", 1);
+    let after_template = modified();
+    assert_ne!(after_template[0], built[0]);
+    assert_eq!(after_template[1..], built[1..]);
+
+    // and so does a data file, even outside the content root.
+    let _data = RestoreOnDrop::new(project_root.join("data.json"));
+    fs::write(project_root.join("data.json"), "{\"title\": \"Changed\"}\n").unwrap();
+    watching.wait_for("recompiled path", 2);
+    let after_data = modified();
+    assert_eq!(after_data[0], after_template[0]);
+    assert_ne!(after_data[1], after_template[1]);
+    assert_eq!(after_data[2], after_template[2]);
+}
+
+#[test]
+fn watching_removes_outputs_of_removed_directories() {
+    let test = IntegrationTest::new("watch_removal")
+        .args(vec!["--watch".to_string(), "--no-cache".to_string()])
+        .timeout(Duration::from_secs(15));
+    let project_root = env::current_dir()
+        .unwrap()
+        .join("tests/integration_test_contents/watch_removal");
+    let output_root = project_root.join("_site");
+
+    // the directories are made here, so the fixture is left as it was whatever happens to them.
+    let moved = project_root.join("blog-moved");
+    let _cleanup = [
+        RemoveOnDrop(project_root.join("src/blog")),
+        RemoveOnDrop(project_root.join("src/docs")),
+        RemoveOnDrop(moved.clone()),
+    ];
+    for dir in ["blog", "docs"] {
+        let dir = project_root.join("src").join(dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("post.typ"), "= Post\n").unwrap();
+        fs::write(dir.join("nested/style.css"), "body {}\n").unwrap();
+    }
+
+    let watching = test.spawn().unwrap();
+    watching.wait_for("compiled project from scratch", 1);
+    assert!(output_root.join("blog/post/index.html").is_file());
+    assert!(output_root.join("docs/nested/style.css").is_file());
+
+    // moving a directory out of the content root only reports the directory itself.
+    fs::rename(project_root.join("src/blog"), &moved).unwrap();
+    watching.wait_for("compiled project from scratch", 2);
+    assert!(!output_root.join("blog").exists());
+
+    fs::remove_dir_all(project_root.join("src/docs")).unwrap();
+    watching.wait_for("compiled project from scratch", 3);
+    assert!(!output_root.join("docs").exists());
+    assert!(output_root.join("index.html").is_file());
+}
+
+#[test]
+fn watching_ignores_the_file_listing() {
+    let test = IntegrationTest::new("watch_file_listing")
+        .args(vec!["--watch".to_string(), "--no-cache".to_string()])
+        .timeout(Duration::from_secs(15));
+    let project_root = env::current_dir()
+        .unwrap()
+        .join("tests/integration_test_contents/watch_file_listing");
+    let _new_page = RemoveOnDrop(project_root.join("src/new.typ"));
+
+    let watching = test.spawn().unwrap();
+    watching.wait_for("compiled project from scratch", 1);
+
+    // a new page is listed in `files.json`, but rewriting it mustn't start another build.
+    fs::write(project_root.join("src/new.typ"), "= New\n").unwrap();
+    watching.wait_for("compiled project from scratch", 2);
+    thread::sleep(Duration::from_secs(2));
+    let stdout = watching.stdout();
+    assert_eq!(
+        stdout.matches("compiled project from scratch").count(),
+        2,
+        "{stdout}"
+    );
+    assert!(project_root.join("_site/new/index.html").is_file());
+}
+
+#[test]
+fn prune_stale_removes_stale_outputs() {
+    let (project_root, output) = IntegrationTest::new("stale_outputs").run().unwrap();
+    assert!(output.status.success());
+    let output_root = project_root.join("_site");
+
+    // as if from sources that were since deleted or renamed, or put there by something else.
+    let stale = [
+        "old-post/index.html",
+        "old.css",
+        "CNAME",
+        ".well-known/security.txt",
+    ];
+    for path in stale {
+        let path = output_root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "stale").unwrap();
+    }
+
+    let (_, output) = IntegrationTest::new("stale_outputs")
+        .keep_output()
+        .run()
+        .unwrap();
+    assert!(output.status.success());
+    for path in stale {
+        assert!(output_root.join(path).exists());
+    }
+
+    let (_, output) = IntegrationTest::new("stale_outputs")
+        .args(vec!["--prune-stale".to_string()])
+        .keep_output()
+        .run()
+        .unwrap();
+    assert!(output.status.success());
+    for path in [
+        "index.html",
+        "post/index.html",
+        "style.css",
+        "CNAME",
+        ".well-known/security.txt",
+    ] {
+        assert!(output_root.join(path).exists());
+    }
+    assert!(!output_root.join("old-post").exists());
+    assert!(!output_root.join("old.css").exists());
+}
+
+#[test]
+fn prune_stale_refuses_output_dir_containing_sources() {
+    let (project_root, output) = IntegrationTest::new("stale_outputs")
+        .args(vec![
+            "--prune-stale".to_string(),
+            "--out".to_string(),
+            ".".to_string(),
+        ])
+        .keep_output()
+        .run()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(!project_root.join("index.html").exists());
+    assert!(project_root.join("src/index.typ").exists());
+}
+
+#[test]
+fn staged_builds_only_replace_output_on_success() {
+    let (project_root, output) = IntegrationTest::new("staged_builds").run().unwrap();
+    assert!(output.status.success());
+    let output_root = project_root.join("_site");
+    assert!(output_root.join("index.html").is_file());
+    fs::write(output_root.join("CNAME"), "example.com").unwrap();
+
+    // a new page that compiles, next to one that doesn't.
+    let new_page = project_root.join("src/new.typ");
+    let broken_page = project_root.join("src/broken.typ");
+    let _new_page = RestoreOnDrop::new(new_page.clone());
+    let broken = RestoreOnDrop::new(broken_page.clone());
+    fs::write(&new_page, "= New\n").unwrap();
+    fs::write(&broken_page, "#doesnt-exist\n").unwrap();
+    let (_, failed) = IntegrationTest::new("staged_builds")
+        .keep_output()
+        .run()
+        .unwrap();
+    let failed_build_exposed = output_root.join("new/index.html").exists();
+    drop(broken);
+    let (_, succeeded) = IntegrationTest::new("staged_builds")
+        .keep_output()
+        .run()
+        .unwrap();
+
+    assert!(!failed.status.success());
+    assert!(!failed_build_exposed);
+    assert!(succeeded.status.success());
+    // only the successful build shows up, and carries over files it doesn't produce.
+    assert!(output_root.join("new/index.html").is_file());
+    assert!(output_root.join("CNAME").is_file());
+    assert!(!project_root.join("._site.staging").exists());
+    assert!(!project_root.join("._site.old").exists());
+}
+
+#[test]
+fn keep_going_reports_every_failure() {
+    let (project_root, output) = IntegrationTest::new("failing_pages")
+        .args(vec![
+            "--keep-going".to_string(),
+            "--out".to_string(),
+            "_site_keep_going".to_string(),
+        ])
+        .output_dir("_site_keep_going")
+        .run()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(!output.status.success());
+    assert!(
+        project_root
+            .join("_site_keep_going/fine/index.html")
+            .is_file()
+    );
+    assert!(stdout.contains("2 files failed to compile:"));
+    assert!(stdout.contains("src/broken-a.typ"));
+    assert!(stdout.contains("src/broken-b.typ"));
+    assert!(stdout.contains("2 of 3 files failed to compile."));
+}
+
+#[test]
+fn without_keep_going_reports_first_failure() {
+    let (project_root, output) = IntegrationTest::new("failing_pages")
+        .args(vec![
+            "--jobs".to_string(),
+            "1".to_string(),
+            "--out".to_string(),
+            "_site_first_failure".to_string(),
+        ])
+        .output_dir("_site_first_failure")
+        .run()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(!output.status.success());
+    // every file is still compiled.
+    assert!(
+        project_root
+            .join("_site_first_failure/fine/index.html")
+            .is_file()
+    );
+    assert!(!stdout.contains("files failed to compile:"));
+    assert!(stdout.contains("1 other files failed to compile too"));
+    assert!(stdout.contains("--keep-going"));
+}
+
+#[test]
+fn report_records_every_file() {
+    let report_path = env::temp_dir().join("compile-typst-site-report_records_every_file.json");
+    let (_, output) = IntegrationTest::new("failing_pages")
+        .args(vec![
+            "--keep-going".to_string(),
+            "--report".to_string(),
+            report_path.to_string_lossy().to_string(),
+            "--out".to_string(),
+            "_site_report".to_string(),
+        ])
+        .output_dir("_site_report")
+        .run()
+        .unwrap();
+    assert!(!output.status.success());
+
+    let report = json::parse(&fs::read_to_string(&report_path).unwrap()).unwrap();
+    fs::remove_file(&report_path).unwrap();
+
+    assert_eq!(report["succeeded"], false);
+    assert!(report["compile_ms"].is_number());
+    assert_eq!(report["totals"]["files"], 3);
+    assert_eq!(report["totals"]["failed"], 2);
+    assert_eq!(report["totals"]["ok"], 1);
+
+    let files: Vec<_> = report["files"].members().collect();
+    let fine = files
+        .iter()
+        .find(|file| file["source"] == "src/fine.typ")
+        .unwrap();
+    assert_eq!(fine["kind"], "compile");
+    assert_eq!(fine["status"], "ok");
+    assert_eq!(fine["output"], "fine/index.html");
+    assert!(fine["typst_ms"].is_number());
+    assert!(fine["output_bytes"].as_u64().unwrap() > 0);
+    assert_eq!(fine["warnings"].len(), 1);
+
+    let broken = files
+        .iter()
+        .find(|file| file["source"] == "src/broken-a.typ")
+        .unwrap();
+    assert_eq!(broken["status"], "failed");
+    assert!(
+        broken["errors"][0]
+            .as_str()
+            .unwrap()
+            .contains("unknown variable: doesnt-exist")
+    );
+}
+
+#[test]
+fn timings_chart_every_process() {
+    let (project_root, output) = IntegrationTest::new("timings")
+        .args(vec!["--timings".to_string()])
+        .output_dir("out")
+        .run()
+        .unwrap();
+    assert!(output.status.success());
+
+    let timings_root = project_root.join("out/cts-timings");
+    let chart = fs::read_to_string(timings_root.join("cts-timing.html")).unwrap();
+    for expected in [
+        "title=\"init: true",
+        "title=\"typst query: src/a.typ",
+        "title=\"typst compile: src/b.typ",
+        "title=\"post-processing: src/a.typ",
+        "<h2>Slowest pages</h2>",
+    ] {
+        assert!(chart.contains(expected), "missing {expected:?}");
+    }
+    // only the latest chart is kept.
+    assert_eq!(fs::read_dir(&timings_root).unwrap().count(), 1);
+    // the chart isn't deployed with the site.
+    assert!(!project_root.join("out/_site/cts-timings").exists());
+}
+
+#[test]
+fn typst_diagnostics_are_attributed_to_pages() {
+    // every page has the same warning, which is only logged per page when verbose.
+    let (_, output) = IntegrationTest::new("failing_pages")
+        .args(vec![
+            "--keep-going".to_string(),
+            "--verbose".to_string(),
+            "--out".to_string(),
+            "_site_diagnostics".to_string(),
+        ])
+        .output_dir("_site_diagnostics")
+        .run()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(!output.status.success());
+    let logged = |level: &str, page: &str| {
+        stdout
+            .lines()
+            .any(|line| line.starts_with(level) && line.contains(&format!("in {page}: ")))
+    };
+    assert!(logged("ERROR", "src/broken-a.typ"));
+    assert!(logged("ERROR", "src/broken-b.typ"));
+    assert!(logged("WARN", "src/fine.typ"));
+    assert!(!logged("ERROR", "src/fine.typ"));
+}
+
+const HTML_EXPORT_WARNING: &str = "html export is under active development";
+
+#[test]
+fn repeated_warnings_are_logged_once() {
+    let (_, output) = IntegrationTest::new("repeated_warnings")
+        .args(vec!["--out".to_string(), "_site_once".to_string()])
+        .output_dir("_site_once")
+        .run()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout.matches(HTML_EXPORT_WARNING).count(), 1);
+    assert!(stdout.contains("emitted by 3 files"));
+}
+
+#[test]
+fn verbose_logs_every_repeated_warning() {
+    let (_, output) = IntegrationTest::new("repeated_warnings")
+        .args(vec![
+            "--verbose".to_string(),
+            "--out".to_string(),
+            "_site_verbose".to_string(),
+        ])
+        .output_dir("_site_verbose")
+        .run()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    for page in ["one", "two", "three"] {
+        assert!(stdout.lines().any(|line| {
+            line.contains(&format!("in src/{page}.typ: ")) && line.contains(HTML_EXPORT_WARNING)
+        }));
+    }
+    assert!(!stdout.contains("emitted by"));
+}
+
+#[test]
+fn suppressed_warnings_are_not_logged() {
+    let (_, output) = IntegrationTest::new("repeated_warnings")
+        .args(vec![
+            "--profile".to_string(),
+            "quiet".to_string(),
+            "--out".to_string(),
+            "_site_quiet".to_string(),
+        ])
+        .output_dir("_site_quiet")
+        .run()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(!stdout.contains(HTML_EXPORT_WARNING));
+}
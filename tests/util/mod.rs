@@ -1,12 +1,16 @@
 //! Testing utilities.
 
 use std::{
-    env, fs, io,
+    env, fs,
+    io::{self, BufRead as _, BufReader},
     path::PathBuf,
-    process::{Command, Output},
-    sync::mpsc::{self, RecvTimeoutError},
+    process::{Child, Command, Output, Stdio},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, RecvTimeoutError},
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 pub struct IntegrationTest {
@@ -14,6 +18,10 @@ pub struct IntegrationTest {
     project_root: PathBuf,
     timeout: Duration,
     args: Vec<String>,
+    /// The output directory to clear before running, relative to the project root.
+    output_dir: PathBuf,
+    /// Whether to clear the output directory before running.
+    clear_output: bool,
 }
 
 impl IntegrationTest {
@@ -27,6 +35,8 @@ impl IntegrationTest {
             project_root,
             timeout: Duration::from_secs(5),
             args: Vec::new(),
+            output_dir: PathBuf::from("_site"),
+            clear_output: true,
         }
     }
 
@@ -35,15 +45,64 @@ impl IntegrationTest {
         self
     }
 
+    pub fn output_dir(mut self, output_dir: &str) -> Self {
+        self.output_dir = PathBuf::from(output_dir);
+        self
+    }
+
+    /// Don't clear the output directory before running, e.g. to test what a previous run left behind.
+    pub fn keep_output(mut self) -> Self {
+        self.clear_output = false;
+        self
+    }
+
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.timeout = timeout;
         self
     }
 
+    /// Starts the test without waiting for it to exit, e.g. to watch for changes. It's killed when dropped.
+    pub fn spawn(&self) -> io::Result<Running> {
+        if self.clear_output {
+            let _dont_validate_removal =
+                fs::remove_dir_all(self.project_root.join(&self.output_dir));
+        }
+
+        let command = env::current_dir()?.join("target/debug/compile-typst-site");
+        let mut child = Command::new(&command)
+            .arg("--path")
+            .arg(&self.project_root)
+            .args(&self.args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdout = Arc::new(Mutex::new(String::new()));
+        let reader = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let lines = Arc::clone(&stdout);
+        // ends when the child is killed and its stdout closes.
+        thread::spawn(move || {
+            for line in reader.lines().map_while(Result::ok) {
+                let mut lines = lines.lock().unwrap();
+                lines.push_str(&line);
+                lines.push('\n');
+            }
+        });
+
+        Ok(Running {
+            child,
+            stdout,
+            timeout: self.timeout,
+        })
+    }
+
     /// Runs the test, returning the project root and the output of the test on success.
     pub fn run(&self) -> io::Result<(PathBuf, Output)> {
         thread::scope(|s| {
-            let _dont_validate_removal = fs::remove_dir_all(self.project_root.join("_site"));
+            if self.clear_output {
+                let _dont_validate_removal =
+                    fs::remove_dir_all(self.project_root.join(&self.output_dir));
+            }
 
             let (tx, rx) = mpsc::channel();
 
@@ -77,3 +136,77 @@ impl IntegrationTest {
         })
     }
 }
+
+/// A test that's still running, started by [`IntegrationTest::spawn`].
+pub struct Running {
+    child: Child,
+    stdout: Arc<Mutex<String>>,
+    timeout: Duration,
+}
+
+impl Running {
+    /// Everything written to stdout so far.
+    pub fn stdout(&self) -> String {
+        self.stdout.lock().unwrap().clone()
+    }
+
+    /// Waits until `needle` has been written to stdout `count` times, returning everything written so far.
+    ///
+    /// Panics if that takes longer than the test's timeout.
+    pub fn wait_for(&self, needle: &str, count: usize) -> String {
+        let start = Instant::now();
+        loop {
+            let stdout = self.stdout.lock().unwrap().clone();
+            if stdout.matches(needle).count() >= count {
+                return stdout;
+            }
+            if start.elapsed() > self.timeout {
+                panic!("timed out waiting for {needle:?} {count} times. stdout: {stdout}");
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let _dont_validate_kill = self.child.kill();
+        let _dont_validate_wait = self.child.wait();
+    }
+}
+
+/// Removes a file or directory when dropped, so a test cleans up what it creates even if it panics.
+pub struct RemoveOnDrop(pub PathBuf);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let _dont_validate_removal = if self.0.is_dir() {
+            fs::remove_dir_all(&self.0)
+        } else {
+            fs::remove_file(&self.0)
+        };
+    }
+}
+
+/// Puts a file back how it was when dropped, or removes it if it didn't exist, so a test can edit fixture sources
+/// and still leave them as they were if it panics.
+pub struct RestoreOnDrop {
+    path: PathBuf,
+    contents: Option<Vec<u8>>,
+}
+
+impl RestoreOnDrop {
+    pub fn new(path: PathBuf) -> Self {
+        let contents = fs::read(&path).ok();
+        Self { path, contents }
+    }
+}
+
+impl Drop for RestoreOnDrop {
+    fn drop(&mut self) {
+        let _dont_validate_restoring = match &self.contents {
+            Some(contents) => fs::write(&self.path, contents),
+            None => fs::remove_file(&self.path),
+        };
+    }
+}